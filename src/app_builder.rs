@@ -1,24 +1,443 @@
+use std::collections::BTreeMap as Map;
+use std::fs::File;
 use std::io;
-use crate::configuration::{Configuration, StorageType};
-use crate::domain::{Candidate, Voter, BallotPaper, VoteOutcome, Scoreboard, VotingMachine};
+use std::sync::Arc;
+use crate::clock::{SystemClock, Timestamp};
+use crate::configuration::{Configuration, SeatAllocationMethod, StorageType, SurplusMethod};
+use crate::domain::{BallotPaper, Candidate, CandidateOutcome, CodesOutcome, ElectionId, ElectionState, Elections, ElectoralRoll, Grade, RoundOutcome, TransitionOutcome, VoteOutcome, Scoreboard, Voter, VotingCode, VotingMachine, WithdrawalPolicy};
+use crate::names::{match_candidate, NameMatch};
 use crate::storage::Storage;
 use crate::storages::memory::Memory;
 use crate::storages::file::FileStore;
+use crate::tallies::majority_judgment::GradeScale;
+use crate::tallies::proportional::{AllocationMethod, ProportionalRule};
+use crate::tallies::score::ScoreRange;
+use crate::tallies::single_transferable_vote::SurplusTransfer;
+use crate::tally::{build_rule, BallotKind, RuleRegistry, TallyOptions, TallyReport};
 use crate::use_cases::*;
 
-fn create_voting_machine(configuration: &Configuration) -> VotingMachine {
-    let candidates: Vec<Candidate> = configuration.candidates.iter().map(|c| Candidate(c.clone())).collect();
+fn tally_options(configuration: &Configuration) -> TallyOptions {
+    TallyOptions {
+        seats: configuration.seats,
+        surplus: match configuration.surplus {
+            SurplusMethod::Gregory => SurplusTransfer::Gregory,
+            SurplusMethod::Meek => SurplusTransfer::Meek,
+        },
+        weights: configuration.weights.clone(),
+        score_range: ScoreRange { min: configuration.min_score, max: configuration.max_score },
+        grades: GradeScale(configuration.grades.iter()
+            .map(|grade| grade.trim())
+            .filter(|grade| !grade.is_empty())
+            .map(|grade| Grade(grade.to_string()))
+            .collect()),
+        budget: configuration.budget,
+    }
+}
+
+fn create_voting_machine(configuration: &Configuration, rules: &RuleRegistry) -> anyhow::Result<VotingMachine> {
+    create_election(configuration, &configuration.candidates, &configuration.method, rules)
+}
+
+/// Builds an election between `candidates` counted with `method`, the other
+/// rule options coming from the configuration.
+fn create_election(configuration: &Configuration, candidates: &[String], method: &str, rules: &RuleRegistry) -> anyhow::Result<VotingMachine> {
+    let candidates: Vec<Candidate> = candidates.iter().map(|c| Candidate(c.clone())).collect();
     let scoreboard = Scoreboard::new(candidates);
-    VotingMachine::new(scoreboard)
+    let rule = build_rule(method, &tally_options(configuration), rules)?;
+    if configuration.write_ins && rule.ballot_kind() != BallotKind::Single {
+        return Err(anyhow::anyhow!("Les votes pour une personne non candidate ne se comptent qu'avec un choix unique, pas avec la règle {}", rule.name()));
+    }
+    let voter_weights = match &configuration.voter_weights {
+        Some(path) => load_voter_weights(path)?,
+        None => Map::new(),
+    };
+    let roll = match &configuration.roll {
+        Some(path) => Some(load_electoral_roll(path)?),
+        None => None,
+    };
+    Ok(VotingMachine::new(scoreboard)
+        .with_rule(rule)
+        .with_voter_weights(voter_weights)
+        .with_roll(roll)
+        .with_max_proxies(configuration.max_proxies)
+        .with_state(ElectionState::Draft)
+        .with_hidden_results(configuration.hide_results)
+        .with_secret_ballot(configuration.secret_ballot)
+        .with_revision(configuration.allow_revision, Vec::new())
+        .with_write_ins(configuration.write_ins, Map::new())
+        .with_schedule(configuration.opens_at.map(Timestamp), configuration.closes_at.map(Timestamp)))
+}
+
+fn load_voter_weights(path: &str) -> anyhow::Result<Map<Voter, usize>> {
+    let weights: Map<String, usize> = serde_json::from_reader(File::open(path)?)?;
+    Ok(weights.into_iter().map(|(voter, weight)| (Voter(voter), weight)).collect())
+}
+
+/// Reads the electoral roll from a JSON list of names, or from a CSV file
+/// whose first column names one voter per line, below an optional header.
+fn load_electoral_roll(path: &str) -> anyhow::Result<ElectoralRoll> {
+    let names: Vec<String> = if path.ends_with(".json") {
+        serde_json::from_reader(File::open(path)?)?
+    } else {
+        parse_roll_csv(&std::fs::read_to_string(path)?)
+    };
+    Ok(ElectoralRoll(names.into_iter().map(Voter).collect()))
+}
+
+/// Titles a roll's first line may give its name column, such as the
+/// `nom,code` line of the code sheet.
+const ROLL_HEADERS: [&str; 5] = ["nom", "name", "votant", "électeur", "electeur"];
+
+fn parse_roll_csv(contents: &str) -> Vec<String> {
+    let mut names: Vec<String> = contents.trim_start_matches('\u{feff}').lines()
+        .map(first_cell)
+        .filter(|name| !name.is_empty())
+        .collect();
+    if names.first().is_some_and(|name| ROLL_HEADERS.contains(&name.to_lowercase().as_str())) {
+        names.remove(0);
+    }
+    names
+}
+
+/// First cell of a CSV line, without its quotes when it is quoted.
+fn first_cell(line: &str) -> String {
+    let line = line.trim();
+    let Some(quoted) = line.strip_prefix('"') else {
+        return line.split([',', ';']).next().unwrap_or_default().trim().to_string();
+    };
+    let mut cell = String::new();
+    let mut chars = quoted.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if chars.peek() == Some(&'"') => {
+                chars.next();
+                cell.push('"');
+            }
+            '"' => break,
+            c => cell.push(c),
+        }
+    }
+    cell.trim().to_string()
+}
+
+fn proportional_rule(configuration: &Configuration) -> Option<ProportionalRule> {
+    let method = match configuration.allocation? {
+        SeatAllocationMethod::DHondt => AllocationMethod::DHondt,
+        SeatAllocationMethod::SainteLague => AllocationMethod::SainteLague,
+        SeatAllocationMethod::Hare => AllocationMethod::HareLargestRemainder,
+        SeatAllocationMethod::Droop => AllocationMethod::DroopLargestRemainder,
+    };
+    Some(ProportionalRule { method, seats: configuration.seats, threshold: configuration.threshold })
+}
+
+/// Parses entries typed as `Alice=Bien, Bob=Passable`, `None` if one of them
+/// has no `=`.
+fn parse_pairs(input: &str) -> Option<Map<String, String>> {
+    split_names(input).iter()
+        .map(|entry| {
+            let (candidate, value) = entry.rsplit_once('=')?;
+            Some((candidate.trim().to_string(), value.trim().to_string()))
+        })
+        .collect()
+}
+
+/// Parses scores typed as `Alice=5, Bob=2`, `None` if one of them is malformed.
+fn parse_scores(input: &str) -> Option<Map<String, usize>> {
+    parse_pairs(input)?.into_iter()
+        .map(|(candidate, score)| Some((candidate, score.parse().ok()?)))
+        .collect()
+}
+
+fn split_names(input: &str) -> Vec<String> {
+    input.split(',').map(|c| c.trim().to_string()).filter(|c| !c.is_empty()).collect()
+}
+
+fn state_name(state: ElectionState) -> &'static str {
+    match state {
+        ElectionState::Draft => "en préparation",
+        ElectionState::Open => "ouvert",
+        ElectionState::Closed => "clos",
+        ElectionState::Certified => "certifié",
+    }
+}
+
+/// Formats a timestamp as a UTC date and time, e.g. `2025-03-14 09:30 UTC`.
+fn format_timestamp(timestamp: Timestamp) -> String {
+    let days = (timestamp.0 / 86_400) as i64;
+    let seconds = timestamp.0 % 86_400;
+    // Civil date from a day count, after Howard Hinnant's algorithm.
+    let shifted = days + 719_468;
+    let era = shifted.div_euclid(146_097);
+    let day_of_era = shifted.rem_euclid(146_097);
+    let year_of_era = (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 { month_index + 3 } else { month_index - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    format!("{:04}-{:02}-{:02} {:02}:{:02} UTC", year, month, day, seconds / 3_600, seconds % 3_600 / 60)
+}
+
+fn print_transition(outcome: TransitionOutcome) {
+    match outcome {
+        TransitionOutcome::Changed(_, state) => println!("Le scrutin est désormais {}", state_name(state)),
+        TransitionOutcome::Refused(current, _) => println!("Transition impossible : le scrutin est {}", state_name(current)),
+    }
+}
+
+fn print_candidate_outcome(outcome: CandidateOutcome) {
+    match outcome {
+        CandidateOutcome::Added(candidate) => println!("{} est candidat(e)", candidate.0),
+        CandidateOutcome::Withdrawn(candidate) => println!("{} s'est retiré(e)", candidate.0),
+        CandidateOutcome::Renamed(from, to) => println!("{} s'appelle désormais {}", from.0, to.0),
+        CandidateOutcome::UnknownCandidate(candidate) => println!("Candidat inconnu : {}", candidate.0),
+        CandidateOutcome::AlreadyRunning(candidate) => println!("{} est déjà candidat(e)", candidate.0),
+        CandidateOutcome::WrittenIn(name) => println!("Des votes sont déjà écrits pour {} : fusionnez-les ou promouvez ce nom", name.0),
+        CandidateOutcome::Merged(from, into) => println!("Les votes écrits pour {} sont désormais comptés pour {}", from.0, into.0),
+        CandidateOutcome::Promoted(candidate) => println!("{} est candidat(e), avec les votes écrits à son nom", candidate.0),
+        CandidateOutcome::Locked(state) => println!("Les candidats ne peuvent plus être modifiés : le scrutin est {}", state_name(state)),
+    }
+}
+
+fn format_candidates(candidates: &[Candidate]) -> String {
+    candidates.iter().map(|c| c.0.as_str()).collect::<Vec<_>>().join(", ")
+}
+
+/// Asks for the voter's code when the election issued codes.
+fn read_code(voting_machine: &VotingMachine) -> io::Result<String> {
+    if !voting_machine.requires_codes() {
+        return Ok(String::new());
+    }
+    println!("Quel est votre code de vote ?");
+    let mut code = String::new();
+    io::stdin().read_line(&mut code)?;
+    Ok(code.trim().to_string())
 }
 
-pub async fn handle_lines<Store: Storage>(configuration: Configuration) -> anyhow::Result<()> {
+/// Writes the code sheet to print, one `nom,code` line per voter.
+fn export_codes(path: &str, codes: &Map<Voter, VotingCode>) -> io::Result<()> {
+    let mut sheet = String::from("nom,code\n");
+    for (voter, code) in codes {
+        let name = match voter.0.contains([',', ';', '"']) {
+            true => format!("\"{}\"", voter.0.replace('"', "\"\"")),
+            false => voter.0.clone(),
+        };
+        sheet.push_str(&format!("{},{}\n", name, code.0));
+    }
+    std::fs::write(path, sheet)
+}
+
+/// Lists the candidates with the number they can be chosen by.
+fn print_candidate_menu(candidates: &[Candidate]) {
+    println!("Candidats :");
+    for (index, candidate) in candidates.iter().enumerate() {
+        println!("{}. {}", index + 1, candidate.0);
+    }
+}
+
+/// Name of the candidate the voter meant, once they confirmed a suggestion;
+/// what they typed when it matches no one or they turned the suggestion down.
+fn resolve_candidate(typed: &str, candidates: &[Candidate]) -> io::Result<String> {
+    match match_candidate(typed, candidates) {
+        NameMatch::Found(candidate) => Ok(candidate.0),
+        NameMatch::Suggested(candidate) => {
+            println!("Vouliez-vous dire {} ? (o/n)", candidate.0);
+            let mut answer = String::new();
+            io::stdin().read_line(&mut answer)?;
+            match answer.trim().to_lowercase().as_str() {
+                "o" | "oui" => Ok(candidate.0),
+                _ => Ok(typed.to_string()),
+            }
+        },
+        NameMatch::Unknown => Ok(typed.to_string()),
+    }
+}
+
+fn resolve_candidates(typed: Vec<String>, candidates: &[Candidate]) -> io::Result<Vec<String>> {
+    typed.iter().map(|name| resolve_candidate(name, candidates)).collect()
+}
+
+fn resolve_candidate_keys<Value>(typed: Map<String, Value>, candidates: &[Candidate]) -> io::Result<Map<String, Value>> {
+    typed.into_iter().map(|(name, value)| Ok((resolve_candidate(&name, candidates)?, value))).collect()
+}
+
+/// Asks the voter for a ballot of the shape the rule counts, `None` if what
+/// they typed cannot be parsed. Candidates may be typed loosely or by their
+/// number in the menu; close misspellings are confirmed with the voter.
+fn read_ballot(voter: String, code: String, ballot_kind: BallotKind, rule_options: &TallyOptions, candidates: &[Candidate]) -> anyhow::Result<Option<BallotPaper>> {
+    print_candidate_menu(candidates);
+    let ballot_paper: BallotPaper = match ballot_kind {
+        BallotKind::Single => {
+            println!("Pour qui voulez-vous voter ? (Laissez vide pour un vote blanc)");
+            let mut candidate_name = String::new();
+            io::stdin().read_line(&mut candidate_name)?;
+
+            let vote_form = VoteForm {
+                voter,
+                candidate: resolve_candidate(candidate_name.trim(), candidates)?,
+                code,
+            };
+            vote_form.into()
+        },
+        BallotKind::Ranked => {
+            println!("Classez les candidats par ordre de préférence, séparés par des virgules (Laissez vide pour un vote blanc)");
+            let mut ranking = String::new();
+            io::stdin().read_line(&mut ranking)?;
+
+            let vote_form = RankedVoteForm {
+                voter,
+                ranking: resolve_candidates(split_names(&ranking), candidates)?,
+                code,
+            };
+            vote_form.into()
+        },
+        BallotKind::Approval => {
+            println!("Quels candidats approuvez-vous ? Séparez-les par des virgules (Laissez vide pour un vote blanc)");
+            let mut approved = String::new();
+            io::stdin().read_line(&mut approved)?;
+
+            let vote_form = ApprovalVoteForm {
+                voter,
+                candidates: resolve_candidates(split_names(&approved), candidates)?,
+                code,
+            };
+            vote_form.into()
+        },
+        BallotKind::Scored => {
+            println!(
+                "Notez les candidats de {} à {} sous la forme Candidat=note, séparés par des virgules (Laissez vide pour un vote blanc)",
+                rule_options.score_range.min, rule_options.score_range.max
+            );
+            let mut scores = String::new();
+            io::stdin().read_line(&mut scores)?;
+
+            let Some(scores) = parse_scores(&scores) else {
+                println!("Format de notes invalide, vote non enregistré");
+                return Ok(None);
+            };
+            let vote_form = ScoreVoteForm {
+                voter,
+                scores: resolve_candidate_keys(scores, candidates)?,
+                code,
+            };
+            vote_form.into()
+        },
+        BallotKind::Points => {
+            println!(
+                "Répartissez jusqu'à {} points sous la forme Candidat=points, séparés par des virgules (Laissez vide pour un vote blanc)",
+                rule_options.budget
+            );
+            let mut points = String::new();
+            io::stdin().read_line(&mut points)?;
+
+            let Some(scores) = parse_scores(&points) else {
+                println!("Format de points invalide, vote non enregistré");
+                return Ok(None);
+            };
+            let vote_form = ScoreVoteForm {
+                voter,
+                scores: resolve_candidate_keys(scores, candidates)?,
+                code,
+            };
+            vote_form.into()
+        },
+        BallotKind::Credits => {
+            println!(
+                "Vous disposez de {} crédits, k voix pour un candidat coûtent k² crédits. Indiquez vos voix sous la forme Candidat=voix, séparées par des virgules (Laissez vide pour un vote blanc)",
+                rule_options.budget
+            );
+            let mut votes = String::new();
+            io::stdin().read_line(&mut votes)?;
+
+            let Some(scores) = parse_scores(&votes) else {
+                println!("Format de voix invalide, vote non enregistré");
+                return Ok(None);
+            };
+            let vote_form = ScoreVoteForm {
+                voter,
+                scores: resolve_candidate_keys(scores, candidates)?,
+                code,
+            };
+            vote_form.into()
+        },
+        BallotKind::Graded => {
+            println!(
+                "Attribuez une mention à chaque candidat sous la forme Candidat=mention ({}), séparés par des virgules (Laissez vide pour un vote blanc)",
+                rule_options.grades.0.iter().map(|grade| grade.0.as_str()).collect::<Vec<_>>().join(", ")
+            );
+            let mut grades = String::new();
+            io::stdin().read_line(&mut grades)?;
+
+            let Some(grades) = parse_pairs(&grades) else {
+                println!("Format de mentions invalide, vote non enregistré");
+                return Ok(None);
+            };
+            let vote_form = GradeVoteForm {
+                voter,
+                grades: resolve_candidate_keys(grades, candidates)?,
+                code,
+            };
+            vote_form.into()
+        },
+    };
+    Ok(Some(ballot_paper))
+}
+
+fn print_outcome(outcome: VoteOutcome, rule_options: &TallyOptions) {
+    match outcome {
+        VoteOutcome::AcceptedVote(_, c) => println!("Vote enregistré pour {}", c.0),
+        VoteOutcome::AcceptedRanking(_, ranking) => println!("Vote enregistré : {}", format_candidates(&ranking)),
+        VoteOutcome::AcceptedApproval(_, approved) => println!("Vote enregistré pour {}", format_candidates(&approved)),
+        VoteOutcome::AcceptedScores(_, scores) => {
+            let scores: Vec<String> = scores.iter().map(|(c, score)| format!("{} {}", c.0, score)).collect();
+            println!("Vote enregistré : {}", scores.join(", "))
+        },
+        VoteOutcome::AcceptedGrades(_, grades) => {
+            let grades: Vec<String> = grades.iter().map(|(c, grade)| format!("{} {}", c.0, grade.0)).collect();
+            println!("Vote enregistré : {}", grades.join(", "))
+        },
+        VoteOutcome::PartiallyInvalidVote(_, unknown) => println!("Vote enregistré, candidats inconnus ignorés : {}", format_candidates(&unknown)),
+        VoteOutcome::BlankVote(_) => println!("Vote blanc enregistré"),
+        VoteOutcome::InvalidVote(_) => println!("Vote nul enregistré (candidat non trouvé)"),
+        VoteOutcome::WriteIn(_, name) => println!("Vote enregistré pour {} (candidat écrit)", name.0),
+        VoteOutcome::OverBudget(_, spent) => println!("Vote refusé : {} points répartis pour {} autorisés", spent, rule_options.budget),
+        VoteOutcome::Delegated(_, delegate) => println!("Vote délégué à {}", delegate.0),
+        VoteOutcome::DelegationCycle(_, delegate) => println!("Délégation refusée : {} vous délègue déjà son vote", delegate.0),
+        VoteOutcome::ProxyAccepted(principal, holder) => println!("{} votera pour {}", holder.0, principal.0),
+        VoteOutcome::ProxyLimitExceeded(holder) => println!("Procuration refusée : {} détient déjà le nombre maximal de procurations", holder.0),
+        VoteOutcome::ProxyNotGranted(principal, holder) => println!("{} n'a pas de procuration de {}", holder.0, principal.0),
+        VoteOutcome::HasAlreadyVoted(_) => println!("Vous avez déjà voté !"),
+        VoteOutcome::ElectionNotOpen(_, state) => println!("Vote refusé : le scrutin est {}", state_name(state)),
+        VoteOutcome::NotYetOpen(_, opens_at) => println!("Vote refusé : le scrutin ouvre le {}", format_timestamp(opens_at)),
+        VoteOutcome::SecretVote(_) => println!("Vote enregistré"),
+        VoteOutcome::DelegationUnavailable(_) => println!("Délégation refusée : le scrutin est à bulletin secret"),
+        VoteOutcome::InvalidCode(_) => println!("Vote refusé : code de vote invalide"),
+        VoteOutcome::CodeAlreadyUsed(_) => println!("Vote refusé : ce code de vote a déjà été utilisé"),
+        VoteOutcome::NotEligible(voter) => println!("Vote refusé : {} n'est pas inscrit(e) sur la liste électorale", voter.0),
+        VoteOutcome::AlreadyClosed(_, closes_at) => println!("Vote refusé : le scrutin a fermé le {}", format_timestamp(closes_at)),
+    }
+}
+
+pub async fn handle_lines<Store: Storage>(configuration: Configuration, rules: RuleRegistry) -> anyhow::Result<()> {
     println!("Bienvenue sur le serveur de vote !");
-    println!("Les commandes valides sont : voter, votants ou score");
+    println!("Les commandes valides sont : voter, deleguer, procuration, mandataire, votants, score, credits, condorcet, cloturer, ouvrir, fermer, certifier, codes, modifications, ajouter-candidat, retirer-candidat, renommer-candidat, fusionner-ecrits, promouvoir-ecrit, scrutins, scrutin ou nouveau-scrutin");
 
-    let voting_machine = create_voting_machine(&configuration);
-    let store = Store::new(voting_machine).await?;
-    let mut controller = VotingController::new(store);
+    let election = ElectionId(configuration.election.clone());
+    let voting_machine = create_voting_machine(&configuration, &rules)?;
+    let store = Store::new(Elections(Map::from([(election.clone(), voting_machine.clone())]))).await?.with_rules(rules.clone());
+    let mut controller = VotingController::new(store).with_clock(Arc::new(SystemClock));
+    if !controller.election_ids().await?.contains(&election) {
+        controller.add_election(&election.0, voting_machine).await?;
+    }
+    controller.select_election(&election.0).await?;
+    let state = controller.get_voting_machine().await?.get_state();
+    println!("Scrutin en cours : {} ({})", election.0, state_name(state));
+    if let Some(opens_at) = configuration.opens_at {
+        println!("Ouverture programmée le {}", format_timestamp(Timestamp(opens_at)));
+    }
+    if let Some(closes_at) = configuration.closes_at {
+        println!("Fermeture programmée le {}", format_timestamp(Timestamp(closes_at)));
+    }
 
     loop {
         let mut input = String::new();
@@ -30,32 +449,94 @@ pub async fn handle_lines<Store: Storage>(configuration: Configuration) -> anyho
                 let mut voter_name = String::new();
                 io::stdin().read_line(&mut voter_name)?;
 
-                println!("Pour qui voulez-vous voter ? (Laissez vide pour un vote blanc)");
-                let mut candidate_name = String::new();
-                io::stdin().read_line(&mut candidate_name)?;
+                let voting_machine = controller.get_voting_machine().await?;
+                let code = read_code(&voting_machine)?;
+                let rule_options = voting_machine.get_rule().options();
+                let candidates: Vec<Candidate> = voting_machine.get_scoreboard().scores.keys().cloned().collect();
+                let Some(ballot_paper) = read_ballot(voter_name.trim().to_string(), code, voting_machine.get_rule().ballot_kind(), &rule_options, &candidates)? else {
+                    continue;
+                };
+                print_outcome(controller.vote(ballot_paper).await?, &rule_options);
+            },
+            "procuration" => {
+                println!("Qui donne procuration ?");
+                let mut principal_name = String::new();
+                io::stdin().read_line(&mut principal_name)?;
+                println!("À qui ?");
+                let mut holder_name = String::new();
+                io::stdin().read_line(&mut holder_name)?;
 
-                let vote_form = VoteForm {
-                    voter: voter_name.trim().to_string(),
-                    candidate: candidate_name.trim().to_string(),
+                let proxy_form = ProxyForm {
+                    principal: principal_name.trim().to_string(),
+                    holder: holder_name.trim().to_string(),
                 };
+                let rule_options = controller.get_voting_machine().await?.get_rule().options();
+                print_outcome(controller.grant_proxy(proxy_form).await?, &rule_options);
+            },
+            "mandataire" => {
+                println!("Quel est votre nom ?");
+                let mut holder_name = String::new();
+                io::stdin().read_line(&mut holder_name)?;
+                println!("Pour qui votez-vous par procuration ?");
+                let mut principal_name = String::new();
+                io::stdin().read_line(&mut principal_name)?;
 
-                match controller.vote(vote_form).await? {
-                    VoteOutcome::AcceptedVote(_, c) => println!("Vote enregistré pour {}", c.0),
-                    VoteOutcome::BlankVote(_) => println!("Vote blanc enregistré"),
-                    VoteOutcome::InvalidVote(_) => println!("Vote nul enregistré (candidat non trouvé)"),
-                    VoteOutcome::HasAlreadyVoted(_) => println!("Vous avez déjà voté !"),
-                }
+                let voting_machine = controller.get_voting_machine().await?;
+                let code = read_code(&voting_machine)?;
+                let rule_options = voting_machine.get_rule().options();
+                let candidates: Vec<Candidate> = voting_machine.get_scoreboard().scores.keys().cloned().collect();
+                let Some(ballot_paper) = read_ballot(principal_name.trim().to_string(), code, voting_machine.get_rule().ballot_kind(), &rule_options, &candidates)? else {
+                    continue;
+                };
+                print_outcome(controller.vote_by_proxy(holder_name.trim(), ballot_paper).await?, &rule_options);
+            },
+            "deleguer" => {
+                println!("Quel est votre nom ?");
+                let mut voter_name = String::new();
+                io::stdin().read_line(&mut voter_name)?;
+                println!("À qui confiez-vous votre vote ?");
+                let mut delegate_name = String::new();
+                io::stdin().read_line(&mut delegate_name)?;
+
+                let voting_machine = controller.get_voting_machine().await?;
+                let delegation_form = DelegationForm {
+                    voter: voter_name.trim().to_string(),
+                    delegate: delegate_name.trim().to_string(),
+                    code: read_code(&voting_machine)?,
+                };
+                let rule_options = voting_machine.get_rule().options();
+                print_outcome(controller.vote(delegation_form).await?, &rule_options);
             },
             "votants" => {
                 let voting_machine = controller.get_voting_machine().await?;
                 
                 println!("Liste des votants :");
                 for votant in &voting_machine.get_voters().0 {
-                    println!("• {}", votant.0);
+                    match voting_machine.get_proxy_votes().get(votant) {
+                        Some(holder) => println!("• {} (par procuration, {})", votant.0, holder.0),
+                        None => println!("• {}", votant.0),
+                    }
+                }
+                if let Some(turnout) = controller.turnout().await? {
+                    let rate = if turnout.registered == 0 { 0.0 } else { 100.0 * turnout.voted as f64 / turnout.registered as f64 };
+                    println!("Participation : {} sur {} inscrits ({:.1} %)", turnout.voted, turnout.registered, rate);
                 }
             },
             "score" => {
                 let voting_machine = controller.get_voting_machine().await?;
+                if !voting_machine.results_visible() {
+                    println!("Les résultats seront publiés à la clôture du scrutin");
+                    continue;
+                }
+
+                for (index, round) in voting_machine.get_archived_rounds().iter().enumerate() {
+                    println!("Résultats du tour {} ({} votant(s)) :", index + 1, round.voters.0.len());
+                    for (candidate, score) in &round.scoreboard.scores {
+                        println!("• {} : {}", candidate.0, score.0);
+                    }
+                    println!("• Blanc : {}", round.scoreboard.blank_score.0);
+                    println!("• Nul : {}", round.scoreboard.invalid_score.0);
+                }
 
                 println!("Scores actuels :");
                 for (candidate, score) in &voting_machine.get_scoreboard().scores {
@@ -63,15 +544,337 @@ pub async fn handle_lines<Store: Storage>(configuration: Configuration) -> anyho
                 }
                 println!("• Blanc : {}", voting_machine.get_scoreboard().blank_score.0);
                 println!("• Nul : {}", voting_machine.get_scoreboard().invalid_score.0);
+                if !voting_machine.get_scoreboard().write_ins.is_empty() {
+                    println!("Candidats écrits :");
+                    for (name, score) in &voting_machine.get_scoreboard().write_ins {
+                        println!("• {} : {}", name.0, score.0);
+                    }
+                }
+
+                if !voting_machine.get_voter_weights().is_empty() {
+                    let head_counts = voting_machine.get_head_counts();
+                    println!("Décompte par tête :");
+                    for (candidate, score) in &head_counts.scores {
+                        println!("• {} : {}", candidate.0, score.0);
+                    }
+                    println!("• Blanc : {}", head_counts.blank_score.0);
+                    println!("• Nul : {}", head_counts.invalid_score.0);
+                }
+
+                if voting_machine.get_rule().awards_points() {
+                    println!("Classement par points :");
+                    for (position, (candidate, score)) in voting_machine.get_scoreboard().ranking().iter().enumerate() {
+                        println!("{}. {} : {} point(s)", position + 1, candidate.0, score.0);
+                    }
+                }
+
+                let rounds = &voting_machine.get_scoreboard().rounds;
+                for (index, round) in rounds.iter().enumerate() {
+                    println!("Tour {} :", index + 1);
+                    for (candidate, score) in &round.scores {
+                        println!("  • {} : {}", candidate.0, score.0);
+                    }
+                    if !round.eliminated.is_empty() {
+                        println!("  Éliminé(s) : {}", format_candidates(&round.eliminated));
+                        for (candidate, score) in &round.transfers {
+                            println!("  → {} reçoit {} bulletin(s)", candidate.0, score.0);
+                        }
+                        println!("  Bulletins épuisés : {}", round.exhausted.0);
+                    }
+                }
+                if !rounds.is_empty() {
+                    match voting_machine.get_scoreboard().runoff_winner() {
+                        Some(winner) => println!("Vainqueur : {}", winner.0),
+                        None => println!("Aucun vainqueur (égalité)"),
+                    }
+                }
+
+                if !voting_machine.get_delegations().is_empty() {
+                    println!("Poids portés par les votants directs :");
+                    for (voter, weight) in controller.carried_weights().await? {
+                        println!("• {} : {}", voter.0, weight);
+                    }
+                }
+
+                if let Some(rule) = proportional_rule(&configuration) {
+                    let allocation = controller.allocate_seats(&rule).await?;
+                    println!("Répartition des {} sièges :", rule.seats);
+                    for (list, seats) in &allocation.seats {
+                        println!("• {} : {}", list.0, seats);
+                    }
+                    if !allocation.below_threshold.is_empty() {
+                        println!("Sous le seuil de {} % : {}", rule.threshold, format_candidates(&allocation.below_threshold));
+                    }
+                }
+
+                match controller.report().await? {
+                    Some(TallyReport::MajorityJudgment(result)) => {
+                        println!("Classement au jugement majoritaire :");
+                        for (position, judged) in result.iter().enumerate() {
+                            let grade = judged.majority_grade.as_ref().map(|grade| grade.0.as_str()).unwrap_or("-");
+                            let distribution: Vec<String> = judged.distribution.iter().map(|count| count.to_string()).collect();
+                            println!("{}. {} : {} ({})", position + 1, judged.candidate.0, grade, distribution.join(" / "));
+                        }
+                    }
+                    Some(TallyReport::Quadratic(result)) => {
+                        println!("Vote quadratique :");
+                        for (candidate, votes) in &result.votes {
+                            println!("• {} : {} voix ({} crédits)", candidate.0, votes, result.credits[candidate]);
+                        }
+                    }
+                    Some(TallyReport::Star(result)) => {
+                        println!("Finale automatique :");
+                        for (candidate, preferred) in &result.preferences {
+                            println!("  • {} préféré sur {} bulletin(s)", candidate.0, preferred);
+                        }
+                        println!("  Sans préférence : {}", result.no_preference);
+                        match &result.winner {
+                            Some(winner) => println!("Vainqueur : {}", winner.0),
+                            None => println!("Aucun vainqueur (égalité)"),
+                        }
+                    }
+                    Some(TallyReport::TransferableVote(result)) => {
+                        println!("Quota : {:.2}", result.quota);
+                        for (index, stage) in result.stages.iter().enumerate() {
+                            println!("Étape {} :", index + 1);
+                            for (candidate, tally) in &stage.tallies {
+                                println!("  • {} : {:.2}", candidate.0, tally);
+                            }
+                            if !stage.elected.is_empty() {
+                                println!("  Élu(s) : {}", format_candidates(&stage.elected));
+                            }
+                            if !stage.eliminated.is_empty() {
+                                println!("  Éliminé(s) : {}", format_candidates(&stage.eliminated));
+                            }
+                            if let Some(candidate) = &stage.surplus_of {
+                                println!("  Transfert de l'excédent de {}", candidate.0);
+                            }
+                            for (candidate, transfer) in &stage.transfers {
+                                println!("  → {} reçoit {:.2}", candidate.0, transfer);
+                            }
+                            if stage.exhausted > 0.0 {
+                                println!("  Voix épuisées : {:.2}", stage.exhausted);
+                            }
+                        }
+                        println!("Élus : {}", format_candidates(&result.elected));
+                    }
+                    Some(TallyReport::Lines(lines)) => {
+                        for line in lines {
+                            println!("{}", line);
+                        }
+                    }
+                    None => {}
+                }
+            },
+            "credits" => {
+                println!("Quel est votre nom ?");
+                let mut voter_name = String::new();
+                io::stdin().read_line(&mut voter_name)?;
+
+                match controller.remaining_credits(voter_name.trim()).await? {
+                    Some(remaining) => println!("Crédits restants : {}", remaining),
+                    None if controller.get_voting_machine().await?.is_secret_ballot() => {
+                        println!("Le vote est secret : les crédits dépensés ne sont pas conservés")
+                    },
+                    None => println!("Ce scrutin n'utilise pas de crédits"),
+                }
+            },
+            "cloturer" => {
+                match controller.close_round().await? {
+                    RoundOutcome::Elected(candidate) => println!("{} est élu(e)", candidate.0),
+                    RoundOutcome::SecondRound(finalists) => println!("Second tour ouvert entre : {}", format_candidates(&finalists)),
+                    RoundOutcome::Undecided => println!("Aucun vainqueur (égalité)"),
+                }
+            },
+            "scrutins" => {
+                println!("Liste des scrutins :");
+                for (election, state) in controller.election_states().await? {
+                    if &election == controller.current_election() {
+                        println!("• {} ({}, en cours)", election.0, state_name(state));
+                    } else {
+                        println!("• {} ({})", election.0, state_name(state));
+                    }
+                }
+            },
+            "scrutin" => {
+                println!("Quel scrutin voulez-vous ouvrir ?");
+                let mut election_name = String::new();
+                io::stdin().read_line(&mut election_name)?;
+
+                match controller.select_election(election_name.trim()).await {
+                    Ok(()) => println!("Scrutin en cours : {}", controller.current_election().0),
+                    Err(error) => println!("{}", error),
+                }
+            },
+            "nouveau-scrutin" => {
+                println!("Nom du nouveau scrutin ?");
+                let mut election_name = String::new();
+                io::stdin().read_line(&mut election_name)?;
+                println!("Candidats, séparés par des virgules ?");
+                let mut candidates = String::new();
+                io::stdin().read_line(&mut candidates)?;
+                println!("Règle de dépouillement ? (Laissez vide pour {})", configuration.method);
+                let mut method = String::new();
+                io::stdin().read_line(&mut method)?;
+
+                let method = match method.trim() {
+                    "" => configuration.method.as_str(),
+                    method => method,
+                };
+                let created = match create_election(&configuration, &split_names(&candidates), method, &rules) {
+                    Ok(voting_machine) => controller.add_election(election_name.trim(), voting_machine).await,
+                    Err(error) => Err(error),
+                };
+                match created {
+                    Ok(()) => println!("Scrutin {} créé", election_name.trim()),
+                    Err(error) => println!("{}", error),
+                }
             },
-            _ => println!("Commande invalide ! Les commandes valides sont : voter, votants ou score"),
+            "ajouter-candidat" => {
+                println!("Nom du candidat ?");
+                let mut candidate_name = String::new();
+                io::stdin().read_line(&mut candidate_name)?;
+
+                print_candidate_outcome(controller.add_candidate(candidate_name.trim()).await?);
+            },
+            "retirer-candidat" => {
+                println!("Quel candidat se retire ?");
+                let mut candidate_name = String::new();
+                io::stdin().read_line(&mut candidate_name)?;
+                println!("Que deviennent les bulletins qui le citent ? (nul, blanc ou report)");
+                let mut policy = String::new();
+                io::stdin().read_line(&mut policy)?;
+
+                let policy = match policy.trim() {
+                    "nul" => WithdrawalPolicy::Invalid,
+                    "blanc" => WithdrawalPolicy::Blank,
+                    "report" => WithdrawalPolicy::Transfer,
+                    _ => {
+                        println!("Choix invalide, candidat non retiré");
+                        continue;
+                    }
+                };
+                print_candidate_outcome(controller.withdraw_candidate(candidate_name.trim(), policy).await?);
+            },
+            "renommer-candidat" => {
+                println!("Quel candidat voulez-vous renommer ?");
+                let mut candidate_name = String::new();
+                io::stdin().read_line(&mut candidate_name)?;
+                println!("Nouveau nom ?");
+                let mut new_name = String::new();
+                io::stdin().read_line(&mut new_name)?;
+
+                print_candidate_outcome(controller.rename_candidate(candidate_name.trim(), new_name.trim()).await?);
+            },
+            "fusionner-ecrits" => {
+                println!("Quel nom écrit voulez-vous fusionner ?");
+                let mut from = String::new();
+                io::stdin().read_line(&mut from)?;
+                println!("Avec quel nom ?");
+                let mut into = String::new();
+                io::stdin().read_line(&mut into)?;
+
+                print_candidate_outcome(controller.merge_write_ins(from.trim(), into.trim()).await?);
+            },
+            "promouvoir-ecrit" => {
+                println!("Quel nom écrit devient candidat ?");
+                let mut name = String::new();
+                io::stdin().read_line(&mut name)?;
+
+                print_candidate_outcome(controller.promote_write_in(name.trim()).await?);
+            },
+            "modifications" => {
+                let revisions = controller.revisions().await?;
+                if revisions.is_empty() {
+                    println!("Aucun vote modifié");
+                }
+                for revision in revisions {
+                    println!("• {} a modifié son vote le {}", revision.voter.0, format_timestamp(revision.at));
+                }
+            },
+            "codes" => {
+                println!("Fichier où exporter les codes ? (Laissez vide pour codes.csv)");
+                let mut path = String::new();
+                io::stdin().read_line(&mut path)?;
+                let path = match path.trim() {
+                    "" => "codes.csv",
+                    path => path,
+                };
+
+                match controller.issue_codes().await? {
+                    CodesOutcome::Issued(codes) => {
+                        export_codes(path, &codes)?;
+                        println!("{} codes générés et exportés dans {}", codes.len(), path);
+                    },
+                    CodesOutcome::NoRoll => println!("Aucune liste électorale : impossible de générer des codes"),
+                    CodesOutcome::Locked(state) => {
+                        println!("Les codes se génèrent avant l'ouverture du scrutin : le scrutin est {}", state_name(state))
+                    },
+                }
+            },
+            "ouvrir" => print_transition(controller.transition(ElectionState::Open).await?),
+            "fermer" => print_transition(controller.transition(ElectionState::Closed).await?),
+            "certifier" => print_transition(controller.transition(ElectionState::Certified).await?),
+            "condorcet" => {
+                if !controller.get_voting_machine().await?.results_visible() {
+                    println!("Les résultats seront publiés à la clôture du scrutin");
+                    continue;
+                }
+                match controller.condorcet_winner().await? {
+                    Some(winner) => println!("Vainqueur de Condorcet : {}", winner.0),
+                    None => println!("Pas de vainqueur de Condorcet"),
+                }
+
+                println!("Classement de Schulze :");
+                for (position, tied) in controller.schulze_ranking().await?.iter().enumerate() {
+                    println!("{}. {}", position + 1, format_candidates(tied));
+                }
+
+                println!("Chemins les plus forts :");
+                for (candidate, paths) in &controller.strongest_paths().await?.0 {
+                    for (other, strength) in paths {
+                        println!("• {} → {} : {}", candidate.0, other.0, strength);
+                    }
+                }
+            },
+            _ => println!("Commande invalide ! Les commandes valides sont : voter, deleguer, procuration, mandataire, votants, score, credits, condorcet, cloturer, ouvrir, fermer, certifier, codes, modifications, ajouter-candidat, retirer-candidat, renommer-candidat, fusionner-ecrits, promouvoir-ecrit, scrutins, scrutin ou nouveau-scrutin"),
         }
     }
 }
 
 pub async fn run_app(configuration: Configuration) -> anyhow::Result<()> {
+    run_app_with_rules(configuration, RuleRegistry::new()).await
+}
+
+/// Runs the application with counting rules defined outside this crate on
+/// top of the builtin ones.
+pub async fn run_app_with_rules(configuration: Configuration, rules: RuleRegistry) -> anyhow::Result<()> {
     match configuration.storage {
-        StorageType::Memory => handle_lines::<Memory>(configuration).await,
-        StorageType::File => handle_lines::<FileStore>(configuration).await,
+        StorageType::Memory => handle_lines::<Memory>(configuration, rules).await,
+        StorageType::File => handle_lines::<FileStore>(configuration, rules).await,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_the_first_column_of_the_roll() {
+        let names = parse_roll_csv("Alice,12 rue des Lilas\n\n\"Bob\";Lyon\n  Claude  \n");
+        assert_eq!(names, vec!["Alice", "Bob", "Claude"]);
+    }
+
+    #[test]
+    fn skips_the_header_of_the_roll() {
+        let names = parse_roll_csv("\u{feff}Nom,code\r\n\"Dupont, Jean\",A1B2\r\n\"Le \"\"Grand\"\"\";C3D4\r\n");
+        assert_eq!(names, vec!["Dupont, Jean", "Le \"Grand\""]);
+    }
+
+    #[test]
+    fn formats_timestamps_as_utc_dates() {
+        assert_eq!(format_timestamp(Timestamp(0)), "1970-01-01 00:00 UTC");
+        assert_eq!(format_timestamp(Timestamp(1_741_944_600)), "2025-03-14 09:30 UTC");
+        assert_eq!(format_timestamp(Timestamp(951_782_400)), "2000-02-29 00:00 UTC");
     }
 }
@@ -0,0 +1,43 @@
+use std::fmt::Debug;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Seconds since the Unix epoch.
+#[derive(Hash, Eq, PartialEq, Debug, Ord, PartialOrd, Clone, Copy)]
+pub struct Timestamp(pub u64);
+
+/// Source of the current time, so that scheduled openings and closings can
+/// be checked without waiting for them.
+pub trait Clock: Debug + Send + Sync {
+    fn now(&self) -> Timestamp;
+}
+
+#[derive(Debug)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Timestamp {
+        let elapsed = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+        Timestamp(elapsed.as_secs())
+    }
+}
+
+/// Clock stopped at a given time.
+#[derive(Debug)]
+pub struct FixedClock(pub Timestamp);
+
+impl Clock for FixedClock {
+    fn now(&self) -> Timestamp {
+        self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn system_clock_is_past_the_epoch() {
+        assert!(SystemClock.now() > Timestamp(0));
+        assert_eq!(FixedClock(Timestamp(42)).now(), Timestamp(42));
+    }
+}
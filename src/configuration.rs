@@ -7,6 +7,21 @@ pub enum StorageType {
     Memory,
 }
 
+#[derive(Clone, Copy, ValueEnum, Debug)]
+pub enum SurplusMethod {
+    Gregory,
+    Meek,
+}
+
+#[derive(Clone, Copy, ValueEnum, Debug)]
+pub enum SeatAllocationMethod {
+    #[value(name = "dhondt")]
+    DHondt,
+    SainteLague,
+    Hare,
+    Droop,
+}
+
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 pub struct Configuration {
@@ -14,11 +29,63 @@ pub struct Configuration {
     pub candidates: Vec<String>,
     #[arg(short = 'm', long, value_delimiter = ',', num_args = 1)]
     pub storage: StorageType,
+    /// Election the session starts on, created from the other options if the
+    /// store does not hold it yet
+    #[arg(long, default_value = "principal")]
+    pub election: String,
+    #[arg(long, default_value = "plurality")]
+    pub method: String,
+    #[arg(long, default_value_t = 1)]
+    pub seats: usize,
+    #[arg(long, value_enum, default_value_t = SurplusMethod::Gregory)]
+    pub surplus: SurplusMethod,
+    #[arg(long, value_delimiter = ',', required_if_eq("method", "positional"))]
+    pub weights: Vec<usize>,
+    #[arg(long, default_value_t = 0)]
+    pub min_score: usize,
+    #[arg(long, default_value_t = 5)]
+    pub max_score: usize,
+    #[arg(long, value_delimiter = ',', default_value = "Excellent,Très bien,Bien,Assez bien,Passable,Insuffisant,À rejeter")]
+    pub grades: Vec<String>,
+    #[arg(long, default_value_t = 5)]
+    pub budget: usize,
+    /// JSON file giving the weight of each voter, e.g. `{"Alice": 120}`
+    #[arg(long)]
+    pub voter_weights: Option<String>,
+    /// Electoral roll, either a JSON list of names or a CSV file with one
+    /// voter per line, their name in the first column
+    #[arg(long)]
+    pub roll: Option<String>,
+    #[arg(long, default_value_t = 1)]
+    pub max_proxies: usize,
+    /// Keep the results hidden until the election is closed
+    #[arg(long)]
+    pub hide_results: bool,
+    /// Keep ballots apart from voters and do not echo the choice back
+    #[arg(long)]
+    pub secret_ballot: bool,
+    /// Let voters replace their ballot until the election closes
+    #[arg(long)]
+    pub allow_revision: bool,
+    /// Count ballots naming someone who is not running as write-ins rather
+    /// than invalid, with the single-choice rules (plurality, two-round) only
+    #[arg(long)]
+    pub write_ins: bool,
+    /// Time the election opens by itself, in seconds since the Unix epoch
+    #[arg(long)]
+    pub opens_at: Option<u64>,
+    /// Time the election closes by itself, in seconds since the Unix epoch
+    #[arg(long)]
+    pub closes_at: Option<u64>,
+    #[arg(long, value_enum)]
+    pub allocation: Option<SeatAllocationMethod>,
+    #[arg(long, default_value_t = 0.0)]
+    pub threshold: f64,
 }
 
 impl Configuration {
+    #[allow(clippy::new_without_default)]
     pub fn new() -> Self {
-        let mut config = Self::parse();
-        config
+        Self::parse()
     }
 }
@@ -1,5 +1,21 @@
+use std::any::Any;
 use std::collections::BTreeMap as Map;
 use std::collections::BTreeSet as Set;
+use std::sync::Arc;
+
+use rand::Rng;
+
+use crate::clock::{Clock, SystemClock, Timestamp};
+use crate::names::normalize_write_in;
+use crate::voting_codes::{generate_code, hash_code};
+use crate::tallies::condorcet::{pairwise_preferences, PairwiseMatrix};
+use crate::liquid::{carried_weights, creates_cycle};
+use crate::tallies::majority_judgment::JudgedCandidate;
+use crate::tallies::plurality::Plurality;
+use crate::tallies::quadratic::QuadraticResult;
+use crate::tallies::score::StarResult;
+use crate::tallies::single_transferable_vote::TransferableVoteResult;
+use crate::tally::{count_invalid, BallotKind, TallyReport, TallyRule};
 
 #[derive(Hash, Eq, PartialEq, Debug, Ord, PartialOrd, Clone)]
 pub struct Voter(pub String);
@@ -10,32 +26,365 @@ pub struct Candidate(pub String);
 #[derive(Debug, Clone)]
 pub struct Score(pub usize);
 
+#[derive(Hash, Eq, PartialEq, Debug, Ord, PartialOrd, Clone)]
+pub struct Grade(pub String);
+
 #[derive(Debug, Clone)]
 pub struct AttendanceSheet(pub Set<Voter>);
 
+/// Voters allowed to take part in an election.
+#[derive(Debug, Clone)]
+pub struct ElectoralRoll(pub Set<Voter>);
+
+/// Registered voters who took part, out of the size of the roll.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Turnout {
+    pub voted: usize,
+    pub registered: usize,
+}
+
+/// Secret handed to a registered voter, valid for a single vote.
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub struct VotingCode(pub String);
+
+/// SHA-256 digest of a voting code, the only trace of it the machine keeps.
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub struct CodeHash(pub String);
+
+#[derive(Hash, Eq, PartialEq, Debug, Ord, PartialOrd, Clone)]
+pub struct ElectionId(pub String);
+
+/// Elections held side by side in one session, each with its own candidates,
+/// attendance sheet and tally rule.
+#[derive(Debug, Clone)]
+pub struct Elections(pub Map<ElectionId, VotingMachine>);
+
+/// One counting round of an instant-runoff tally. `eliminated` lists the
+/// candidates dropped at the end of the round, `transfers` the ballots they
+/// passed on to each continuing candidate and `exhausted` the ballots left
+/// without any continuing preference.
+#[derive(Debug, Clone)]
+pub struct Round {
+    pub scores: Map<Candidate, Score>,
+    pub eliminated: Vec<Candidate>,
+    pub transfers: Map<Candidate, Score>,
+    pub exhausted: Score,
+}
+
 #[derive(Debug, Clone)]
 pub struct Scoreboard{
     pub scores: Map<Candidate, Score>,
     pub blank_score: Score,
     pub invalid_score: Score,
+    pub rounds: Vec<Round>,
+    /// Votes for names written in on the ballots, under their normalized
+    /// spelling, kept apart from the candidates'.
+    pub write_ins: Map<Candidate, Score>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Ballot {
+    Single(Option<Candidate>),
+    Ranked(Vec<Candidate>),
+    Approval(Set<Candidate>),
+    Scored(Map<Candidate, usize>),
+    Graded(Map<Candidate, Grade>),
+    /// The voter hands their vote to another voter instead of choosing.
+    Delegated(Voter),
+    /// Ballot void as a whole, such as one for a withdrawn candidate whose
+    /// ballots were declared invalid.
+    Invalid,
+}
+
+/// Contents of the counted ballots, kept for the tallies that need more than
+/// the aggregated scores.
+#[derive(Debug, Clone)]
+pub struct BallotBox(pub Vec<WeightedBallot>);
+
+/// Ballot in the box with the weight it counts for: the voter's own weight
+/// plus the weight delegated to them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WeightedBallot {
+    pub ballot: Ballot,
+    pub weight: usize,
+}
+
+impl BallotBox {
+    /// Preference order of every ballot expressing one, with its weight.
+    pub fn weighted_preferences(&self) -> Vec<(Vec<Candidate>, usize)> {
+        self.0.iter()
+            .filter_map(|entry| entry.ballot.preferences().map(|preferences| (preferences, entry.weight)))
+            .collect()
+    }
+}
+
+impl From<Vec<Ballot>> for BallotBox {
+    /// Ballot box of ballots weighing 1 each.
+    fn from(ballots: Vec<Ballot>) -> Self {
+        BallotBox(ballots.into_iter().map(|ballot| WeightedBallot { ballot, weight: 1 }).collect())
+    }
 }
 
 pub struct BallotPaper {
     pub voter : Voter,
-    pub candidate: Option<Candidate>,
+    pub ballot: Ballot,
+    /// One-time code proving the voter's identity, when the election issued
+    /// codes.
+    pub code: Option<VotingCode>,
 }
 
 pub enum VoteOutcome {
     AcceptedVote(Voter, Candidate),
+    AcceptedRanking(Voter, Vec<Candidate>),
+    AcceptedApproval(Voter, Vec<Candidate>),
+    AcceptedScores(Voter, Map<Candidate, usize>),
+    AcceptedGrades(Voter, Map<Candidate, Grade>),
+    /// The ballot was counted for its known candidates; the unknown ones listed
+    /// here were ignored.
+    PartiallyInvalidVote(Voter, Vec<Candidate>),
     BlankVote(Voter),
     InvalidVote(Voter),
+    /// The ballot spends more points than the budget allows; it was not
+    /// counted and the voter may vote again.
+    OverBudget(Voter, usize),
+    /// The first voter's vote now follows the second one's.
+    Delegated(Voter, Voter),
+    /// The delegation was refused as it would make the chain loop.
+    DelegationCycle(Voter, Voter),
+    /// The second voter may now vote on behalf of the first one.
+    ProxyAccepted(Voter, Voter),
+    /// The proxy holder already holds as many proxies as allowed.
+    ProxyLimitExceeded(Voter),
+    /// The principal gave no proxy to the voter casting their ballot.
+    ProxyNotGranted(Voter, Voter),
     HasAlreadyVoted(Voter),
+    /// The election does not take votes in its current state.
+    ElectionNotOpen(Voter, ElectionState),
+    /// The election opens at the given time.
+    NotYetOpen(Voter, Timestamp),
+    /// The election closed at the given time.
+    AlreadyClosed(Voter, Timestamp),
+    /// The voter is not on the electoral roll.
+    NotEligible(Voter),
+    /// The voting code is missing or does not match the voter's.
+    InvalidCode(Voter),
+    /// The voter's code was already used to vote.
+    CodeAlreadyUsed(Voter),
+    /// The ballot named someone who is not running; it was counted apart
+    /// under the normalized name.
+    WriteIn(Voter, Candidate),
+    /// The ballot was counted; its contents are not given back as the
+    /// election is held by secret ballot.
+    SecretVote(Voter),
+    /// Delegating would tie the delegate's ballot to the voters it counts
+    /// for, which a secret ballot forbids.
+    DelegationUnavailable(Voter),
+}
+
+/// Phase of an election. Candidates are set up in draft, votes are only taken
+/// while open and the results of a closed election are final once certified.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ElectionState {
+    Draft,
+    Open,
+    Closed,
+    Certified,
+}
+
+/// What becomes of the ballots naming a withdrawn candidate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WithdrawalPolicy {
+    /// The whole ballot is counted as invalid.
+    Invalid,
+    /// The whole ballot is counted as blank.
+    Blank,
+    /// The candidate is struck off the ballot, the later preferences of a
+    /// ranking moving up. A ballot left empty is counted as blank.
+    Transfer,
+}
+
+pub enum CandidateOutcome {
+    Added(Candidate),
+    Withdrawn(Candidate),
+    /// The first candidate now runs under the second name.
+    Renamed(Candidate, Candidate),
+    UnknownCandidate(Candidate),
+    AlreadyRunning(Candidate),
+    /// The name is already written in on ballots; merge or promote it.
+    WrittenIn(Candidate),
+    /// The votes written in for the first name now go to the second one.
+    Merged(Candidate, Candidate),
+    /// The name written in now runs as a candidate, with its votes.
+    Promoted(Candidate),
+    /// Candidates cannot be changed in the election's current state.
+    Locked(ElectionState),
+}
+
+pub enum CodesOutcome {
+    /// A fresh code for every voter on the roll.
+    Issued(Map<Voter, VotingCode>),
+    /// The election has no roll to issue codes for.
+    NoRoll,
+    /// Codes can only be issued before the election opens.
+    Locked(ElectionState),
+}
+
+pub enum TransitionOutcome {
+    /// The election moved from the first state to the second one.
+    Changed(ElectionState, ElectionState),
+    /// The election cannot move from the first state to the second one.
+    Refused(ElectionState, ElectionState),
 }
 
 #[derive(Debug, Clone)]
 pub struct VotingMachine{
     voters : AttendanceSheet,
     scoreboard: Scoreboard,
+    ballots: BallotBox,
+    rule: Arc<dyn TallyRule>,
+    archived_rounds: Vec<ArchivedRound>,
+    /// Points or credits each voter spent, for the rules with a budget.
+    spent_credits: Map<Voter, usize>,
+    /// Weight of each registered voter; voters missing from it weigh 1.
+    voter_weights: Map<Voter, usize>,
+    /// Same count as `scoreboard`, every ballot weighing 1.
+    head_counts: Scoreboard,
+    /// Delegate chosen by each voter who delegated their vote.
+    delegations: Map<Voter, Voter>,
+    /// Ballot of each direct voter, recounted when the weight they carry
+    /// changes.
+    cast_ballots: Map<Voter, Ballot>,
+    /// Holder of each proxy given by a principal.
+    proxies: Map<Voter, Voter>,
+    max_proxies: usize,
+    /// Principals on the attendance sheet whose ballot was cast by proxy, with
+    /// the holder who cast it.
+    proxy_votes: Map<Voter, Voter>,
+    state: ElectionState,
+    /// Whether the results stay hidden until the election is closed.
+    hide_results: bool,
+    /// Times the election opens and closes by itself.
+    opens_at: Option<Timestamp>,
+    closes_at: Option<Timestamp>,
+    clock: Arc<dyn Clock>,
+    /// Voters allowed to vote; anyone may vote when there is none.
+    roll: Option<ElectoralRoll>,
+    /// Hash of each registered voter's code, once codes were issued.
+    code_hashes: Map<Voter, CodeHash>,
+    /// Voters whose code was used.
+    used_codes: Set<Voter>,
+    /// Whether ballots are kept apart from voters: only the attendance sheet
+    /// names them.
+    secret_ballot: bool,
+    /// Whether a voter may replace their ballot while the election is open.
+    /// Their current ballot must be known, so it has no effect on a secret
+    /// ballot.
+    allow_revision: bool,
+    revisions: Vec<Revision>,
+    /// Whether ballots naming someone who is not running are counted as
+    /// write-ins rather than invalid, for rules taking a single choice.
+    accept_write_ins: bool,
+    /// Normalized write-in spellings merged into another one.
+    write_in_aliases: Map<Candidate, Candidate>,
+}
+
+/// A ballot replaced by a later one from the same voter, kept for audit.
+#[derive(Debug, Clone)]
+pub struct Revision {
+    pub voter: Voter,
+    pub previous: Ballot,
+    pub replacement: Ballot,
+    pub at: Timestamp,
+}
+
+/// Results of a closed voting round, kept once the next round has opened.
+#[derive(Debug, Clone)]
+pub struct ArchivedRound {
+    pub voters: AttendanceSheet,
+    pub scoreboard: Scoreboard,
+    pub head_counts: Scoreboard,
+    pub ballots: BallotBox,
+}
+
+pub enum RoundOutcome {
+    Elected(Candidate),
+    SecondRound(Vec<Candidate>),
+    Undecided,
+}
+
+impl Ballot {
+    /// Preference order expressed by the ballot, `None` for a blank ballot.
+    pub fn preferences(&self) -> Option<Vec<Candidate>> {
+        match self {
+            Ballot::Single(None) => None,
+            Ballot::Single(Some(candidate)) => Some(vec![candidate.clone()]),
+            Ballot::Ranked(ranking) if ranking.is_empty() => None,
+            Ballot::Ranked(ranking) => Some(ranking.clone()),
+            Ballot::Approval(_) | Ballot::Scored(_) | Ballot::Graded(_) | Ballot::Delegated(_) | Ballot::Invalid => None,
+        }
+    }
+
+    pub fn mentions(&self, candidate: &Candidate) -> bool {
+        match self {
+            Ballot::Single(choice) => choice.as_ref() == Some(candidate),
+            Ballot::Ranked(ranking) => ranking.contains(candidate),
+            Ballot::Approval(approved) => approved.contains(candidate),
+            Ballot::Scored(scores) => scores.contains_key(candidate),
+            Ballot::Graded(grades) => grades.contains_key(candidate),
+            Ballot::Delegated(_) | Ballot::Invalid => false,
+        }
+    }
+
+    /// Same ballot without `candidate`, later preferences moving up.
+    pub fn without(&self, candidate: &Candidate) -> Ballot {
+        match self {
+            Ballot::Single(choice) => Ballot::Single(choice.clone().filter(|choice| choice != candidate)),
+            Ballot::Ranked(ranking) => Ballot::Ranked(ranking.iter().filter(|ranked| *ranked != candidate).cloned().collect()),
+            Ballot::Approval(approved) => Ballot::Approval(approved.iter().filter(|approved| *approved != candidate).cloned().collect()),
+            Ballot::Scored(scores) => Ballot::Scored(scores.iter().filter(|(scored, _)| *scored != candidate).map(|(scored, score)| (scored.clone(), *score)).collect()),
+            Ballot::Graded(grades) => Ballot::Graded(grades.iter().filter(|(graded, _)| *graded != candidate).map(|(graded, grade)| (graded.clone(), grade.clone())).collect()),
+            Ballot::Delegated(delegate) => Ballot::Delegated(delegate.clone()),
+            Ballot::Invalid => Ballot::Invalid,
+        }
+    }
+
+    /// Blank ballot of the same shape.
+    pub fn blank(&self) -> Ballot {
+        match self {
+            Ballot::Single(_) => Ballot::Single(None),
+            Ballot::Ranked(_) => Ballot::Ranked(Vec::new()),
+            Ballot::Approval(_) => Ballot::Approval(Set::new()),
+            Ballot::Scored(_) => Ballot::Scored(Map::new()),
+            Ballot::Graded(_) => Ballot::Graded(Map::new()),
+            Ballot::Delegated(delegate) => Ballot::Delegated(delegate.clone()),
+            Ballot::Invalid => Ballot::Invalid,
+        }
+    }
+
+    pub fn renamed(&self, from: &Candidate, to: &Candidate) -> Ballot {
+        let rename = |candidate: &Candidate| if candidate == from { to.clone() } else { candidate.clone() };
+        match self {
+            Ballot::Single(choice) => Ballot::Single(choice.as_ref().map(rename)),
+            Ballot::Ranked(ranking) => Ballot::Ranked(ranking.iter().map(rename).collect()),
+            Ballot::Approval(approved) => Ballot::Approval(approved.iter().map(rename).collect()),
+            Ballot::Scored(scores) => Ballot::Scored(scores.iter().map(|(candidate, score)| (rename(candidate), *score)).collect()),
+            Ballot::Graded(grades) => Ballot::Graded(grades.iter().map(|(candidate, grade)| (rename(candidate), grade.clone())).collect()),
+            Ballot::Delegated(delegate) => Ballot::Delegated(delegate.clone()),
+            Ballot::Invalid => Ballot::Invalid,
+        }
+    }
+}
+
+impl Default for ElectionId {
+    fn default() -> Self {
+        ElectionId(String::from("principal"))
+    }
+}
+
+impl From<VotingMachine> for Elections {
+    fn from(machine: VotingMachine) -> Self {
+        Elections(Map::from([(ElectionId::default(), machine)]))
+    }
 }
 
 impl Scoreboard {
@@ -48,7 +397,62 @@ impl Scoreboard {
             scores,
             blank_score: Score(0),
             invalid_score: Score(0),
+            rounds: Vec::new(),
+            write_ins: Map::new(),
+        }
+    }
+
+    /// Candidates ordered by decreasing score, ties kept in name order.
+    pub fn ranking(&self) -> Vec<(&Candidate, &Score)> {
+        let mut ranking: Vec<(&Candidate, &Score)> = self.scores.iter().collect();
+        ranking.sort_by_key(|(_, score)| std::cmp::Reverse(score.0));
+        ranking
+    }
+
+    /// Adds the scores, blank and invalid votes of `counted`, each multiplied
+    /// by `weight`.
+    pub fn add_weighted(&mut self, counted: &Scoreboard, weight: usize) {
+        for (candidate, score) in &counted.scores {
+            if let Some(total) = self.scores.get_mut(candidate) {
+                total.0 += score.0 * weight;
+            }
+        }
+        for (name, score) in &counted.write_ins {
+            self.write_ins.entry(name.clone()).or_insert(Score(0)).0 += score.0 * weight;
+        }
+        self.blank_score.0 += counted.blank_score.0 * weight;
+        self.invalid_score.0 += counted.invalid_score.0 * weight;
+    }
+
+    pub fn subtract_weighted(&mut self, counted: &Scoreboard, weight: usize) {
+        for (candidate, score) in &counted.scores {
+            if let Some(total) = self.scores.get_mut(candidate) {
+                total.0 -= score.0 * weight;
+            }
+        }
+        for (name, score) in &counted.write_ins {
+            if let Some(total) = self.write_ins.get_mut(name) {
+                total.0 -= score.0 * weight;
+                if total.0 == 0 {
+                    self.write_ins.remove(name);
+                }
+            }
         }
+        self.blank_score.0 -= counted.blank_score.0 * weight;
+        self.invalid_score.0 -= counted.invalid_score.0 * weight;
+    }
+
+    /// Winner of the instant-runoff tally: the candidate holding a majority
+    /// of the continuing ballots in the last round, or the last one standing.
+    pub fn runoff_winner(&self) -> Option<&Candidate> {
+        let last_round = self.rounds.last()?;
+        if last_round.scores.len() == 1 {
+            return last_round.scores.keys().next();
+        }
+        let total: usize = last_round.scores.values().map(|score| score.0).sum();
+        last_round.scores.iter()
+            .find(|(_, score)| score.0 * 2 > total)
+            .map(|(candidate, _)| candidate)
     }
 }
 
@@ -56,113 +460,1611 @@ impl VotingMachine {
     pub fn new(scoreboard1 : Scoreboard) -> Self {
         VotingMachine {
             voters: AttendanceSheet(Set::new()),
+            head_counts: scoreboard1.clone(),
             scoreboard: scoreboard1,
+            ballots: BallotBox(Vec::new()),
+            rule: Arc::new(Plurality),
+            archived_rounds: Vec::new(),
+            spent_credits: Map::new(),
+            voter_weights: Map::new(),
+            delegations: Map::new(),
+            cast_ballots: Map::new(),
+            proxies: Map::new(),
+            max_proxies: 1,
+            proxy_votes: Map::new(),
+            state: ElectionState::Open,
+            hide_results: false,
+            opens_at: None,
+            closes_at: None,
+            clock: Arc::new(SystemClock),
+            roll: None,
+            code_hashes: Map::new(),
+            used_codes: Set::new(),
+            secret_ballot: false,
+            allow_revision: false,
+            revisions: Vec::new(),
+            accept_write_ins: false,
+            write_in_aliases: Map::new(),
         }
     }
 
+    pub fn with_write_ins(mut self, accept_write_ins: bool, write_in_aliases: Map<Candidate, Candidate>) -> Self {
+        self.accept_write_ins = accept_write_ins;
+        self.write_in_aliases = write_in_aliases;
+        self
+    }
+
+    pub fn accepts_write_ins(&self) -> bool {
+        self.accept_write_ins
+    }
+
+    /// Whether write-ins are counted: only single-choice ballots name one
+    /// person who can be told apart from the candidates.
+    fn takes_write_ins(&self) -> bool {
+        self.accept_write_ins && self.rule.ballot_kind() == BallotKind::Single
+    }
+
+    pub fn get_write_in_aliases(&self) -> &Map<Candidate, Candidate> {
+        &self.write_in_aliases
+    }
+
+    pub fn with_revision(mut self, allow_revision: bool, revisions: Vec<Revision>) -> Self {
+        self.allow_revision = allow_revision;
+        self.revisions = revisions;
+        self
+    }
+
+    pub fn allows_revision(&self) -> bool {
+        self.allow_revision
+    }
+
+    /// Ballots replaced so far, oldest first.
+    pub fn get_revisions(&self) -> &[Revision] {
+        &self.revisions
+    }
+
+    pub fn with_secret_ballot(mut self, secret_ballot: bool) -> Self {
+        self.secret_ballot = secret_ballot;
+        self
+    }
+
+    pub fn is_secret_ballot(&self) -> bool {
+        self.secret_ballot
+    }
+
+    pub fn with_codes(mut self, code_hashes: Map<Voter, CodeHash>, used_codes: Set<Voter>) -> Self {
+        self.code_hashes = code_hashes;
+        self.used_codes = used_codes;
+        self
+    }
+
+    pub fn with_roll(mut self, roll: Option<ElectoralRoll>) -> Self {
+        self.roll = roll;
+        self
+    }
+
+    pub fn with_schedule(mut self, opens_at: Option<Timestamp>, closes_at: Option<Timestamp>) -> Self {
+        self.opens_at = opens_at;
+        self.closes_at = closes_at;
+        self
+    }
+
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    pub fn with_state(mut self, state: ElectionState) -> Self {
+        self.state = state;
+        self
+    }
+
+    pub fn with_hidden_results(mut self, hide_results: bool) -> Self {
+        self.hide_results = hide_results;
+        self
+    }
+
+    pub fn with_archived_rounds(mut self, archived_rounds: Vec<ArchivedRound>) -> Self {
+        self.archived_rounds = archived_rounds;
+        self
+    }
+
+    pub fn with_rule(mut self, rule: Arc<dyn TallyRule>) -> Self {
+        self.rule = rule;
+        self
+    }
+
+    pub fn with_spent_credits(mut self, spent_credits: Map<Voter, usize>) -> Self {
+        self.spent_credits = spent_credits;
+        self
+    }
+
+    pub fn with_voter_weights(mut self, voter_weights: Map<Voter, usize>) -> Self {
+        self.voter_weights = voter_weights;
+        self
+    }
+
+    pub fn with_head_counts(mut self, head_counts: Scoreboard) -> Self {
+        self.head_counts = head_counts;
+        self
+    }
+
+    pub fn with_delegations(mut self, delegations: Map<Voter, Voter>) -> Self {
+        self.delegations = delegations;
+        self
+    }
+
+    pub fn with_cast_ballots(mut self, cast_ballots: Map<Voter, Ballot>) -> Self {
+        self.cast_ballots = cast_ballots;
+        self
+    }
+
+    pub fn with_proxies(mut self, proxies: Map<Voter, Voter>, proxy_votes: Map<Voter, Voter>) -> Self {
+        self.proxies = proxies;
+        self.proxy_votes = proxy_votes;
+        self
+    }
+
+    pub fn with_max_proxies(mut self, max_proxies: usize) -> Self {
+        self.max_proxies = max_proxies;
+        self
+    }
+
+    pub fn with_ballots(mut self, ballots: BallotBox) -> Self {
+        self.ballots = ballots;
+        self
+    }
+
+    /// Counts a ballot. A voter who delegated their vote may still vote
+    /// directly, which cancels the delegation.
     pub fn vote(&mut self, ballot_paper: BallotPaper) -> VoteOutcome {
-        if self.voters.0.contains(&ballot_paper.voter) {
-            return VoteOutcome::HasAlreadyVoted(ballot_paper.voter.clone());
-        }
-    
-        self.voters.0.insert(ballot_paper.voter.clone());
-    
-        match ballot_paper.candidate {
-            None => {
-                self.scoreboard.blank_score.0 += 1;
-                VoteOutcome::BlankVote(ballot_paper.voter.clone())
-            }
-            Some(candidate) => {
-                if let Some(score) = self.scoreboard.scores.get_mut(&candidate) {
-                    score.0 += 1;
-                    VoteOutcome::AcceptedVote(ballot_paper.voter.clone(), candidate)
-                } else {
-                    self.scoreboard.invalid_score.0 += 1;
-                    VoteOutcome::InvalidVote(ballot_paper.voter)
+        let voter = ballot_paper.voter.clone();
+        if let Some(refusal) = self.refuse_outside_opening(&voter) {
+            return refusal;
+        }
+        if !self.is_eligible(&voter) {
+            return VoteOutcome::NotEligible(voter);
+        }
+        if let Some(refusal) = self.check_code(&voter, ballot_paper.code.as_ref()) {
+            return refusal;
+        }
+        if self.voters.0.contains(&voter) && !self.delegations.contains_key(&voter) {
+            if self.allow_revision && !matches!(ballot_paper.ballot, Ballot::Delegated(_)) {
+                if let Some(previous) = self.cast_ballots.get(&voter).cloned() {
+                    return self.revise(voter, previous, ballot_paper.ballot);
                 }
             }
+            return VoteOutcome::HasAlreadyVoted(voter);
+        }
+        if let Ballot::Delegated(delegate) = &ballot_paper.ballot {
+            if self.secret_ballot {
+                return VoteOutcome::DelegationUnavailable(voter);
+            }
+            if !self.is_eligible(delegate) {
+                return VoteOutcome::NotEligible(delegate.clone());
+            }
+            return self.delegate(voter, delegate.clone());
         }
+
+        let ballot = self.resolve_write_in(ballot_paper.ballot);
+        let mut counted = Scoreboard::new(self.scoreboard.scores.keys().cloned().collect());
+        let outcome = self.count(ballot_paper.voter, &ballot, &mut counted);
+        if let VoteOutcome::OverBudget(_, _) = &outcome {
+            return outcome;
+        }
+
+        let carried_before = self.carried_weights();
+        self.voters.0.insert(voter.clone());
+        self.use_code(&voter);
+        self.delegations.remove(&voter);
+        self.proxies.remove(&voter);
+        self.head_counts.add_weighted(&counted, 1);
+        let weight = if self.secret_ballot {
+            // Nothing ties the ballot to the voter, so it is counted once
+            // with their own weight.
+            let weight = self.voter_weight(&voter);
+            self.scoreboard.add_weighted(&counted, weight);
+            weight
+        } else {
+            self.cast_ballots.insert(voter.clone(), ballot.clone());
+            self.reweigh(carried_before);
+            self.record_spending(&voter, &ballot, &outcome);
+            self.carried_weights().get(&voter).copied().unwrap_or(0)
+        };
+        if counts_for_candidates(&outcome) {
+            let entry = WeightedBallot { ballot, weight };
+            if self.secret_ballot {
+                // Dropped in at random, the ballot cannot be matched with the
+                // order voters came in.
+                let slot = rand::thread_rng().gen_range(0..=self.ballots.0.len());
+                self.ballots.0.insert(slot, entry);
+            } else {
+                self.ballots.0.push(entry);
+            }
+        }
+        self.rule.tally(&self.ballots, &mut self.scoreboard);
+        if self.secret_ballot {
+            return VoteOutcome::SecretVote(voter);
+        }
+        outcome
     }
 
-    pub fn get_scoreboard(&self) -> &Scoreboard {
-        &self.scoreboard
+    /// Counts a ballot with the rule, a single vote for someone who is not
+    /// running going to the write-ins when they are accepted.
+    fn count(&self, voter: Voter, ballot: &Ballot, counted: &mut Scoreboard) -> VoteOutcome {
+        if *ballot == Ballot::Invalid {
+            return count_invalid(voter, counted);
+        }
+        let outcome = self.rule.count(voter.clone(), ballot, counted);
+        match (ballot, &outcome) {
+            (Ballot::Single(Some(name)), VoteOutcome::InvalidVote(_)) if self.takes_write_ins() && !self.scoreboard.scores.contains_key(name) => {
+                let name = self.write_in_name(name);
+                counted.invalid_score.0 -= 1;
+                counted.write_ins.entry(name.clone()).or_insert(Score(0)).0 += 1;
+                VoteOutcome::WriteIn(voter, name)
+            }
+            _ => outcome,
+        }
     }
 
-    pub fn get_voters(&self) -> &AttendanceSheet {
-        &self.voters
+    /// Normalized spelling a written-in name is counted under, merges
+    /// followed.
+    fn write_in_name(&self, name: &Candidate) -> Candidate {
+        aliased_write_in(&self.write_in_aliases, name)
     }
 
-    pub fn recover_from(voters: AttendanceSheet, scoreboard: Scoreboard) -> Self {
-        Self {voters, scoreboard}
+    /// Ballot naming a candidate under a spelling that normalizes to their
+    /// name, once write-ins are accepted, rewritten with the exact name.
+    fn resolve_write_in(&self, ballot: Ballot) -> Ballot {
+        match &ballot {
+            Ballot::Single(Some(name)) if self.takes_write_ins() && !self.scoreboard.scores.contains_key(name) => {
+                let resolved = self.write_in_name(name);
+                if self.scoreboard.scores.contains_key(&resolved) {
+                    Ballot::Single(Some(resolved))
+                } else {
+                    ballot
+                }
+            }
+            _ => ballot,
+        }
     }
-}
 
-#[cfg(test)]
-mod tests{
-    use super::*;
+    /// Moves the votes written in for `from` to `into`, for the ballots
+    /// already counted and the ones to come. When `into` is running, the
+    /// ballots are rewritten with their name and counted for them.
+    pub fn merge_write_ins(&mut self, from: &Candidate, into: &Candidate) -> CandidateOutcome {
+        if self.state == ElectionState::Certified {
+            return CandidateOutcome::Locked(self.state);
+        }
+        let from = self.write_in_name(from);
+        let into = match self.scoreboard.scores.contains_key(into) {
+            true => into.clone(),
+            false => self.write_in_name(into),
+        };
+        if from == into || !self.scoreboard.write_ins.contains_key(&from) {
+            return CandidateOutcome::UnknownCandidate(from);
+        }
+
+        if self.scoreboard.scores.contains_key(&into) {
+            self.absorb_write_ins(&from, &into);
+        } else {
+            for scoreboard in [&mut self.scoreboard, &mut self.head_counts] {
+                if let Some(score) = scoreboard.write_ins.remove(&from) {
+                    scoreboard.write_ins.entry(into.clone()).or_insert(Score(0)).0 += score.0;
+                }
+            }
+        }
+        for target in self.write_in_aliases.values_mut() {
+            if *target == from {
+                *target = into.clone();
+            }
+        }
+        self.write_in_aliases.insert(from.clone(), into.clone());
+        CandidateOutcome::Merged(from, into)
+    }
 
+    /// Makes a written-in name a candidate, with the votes written in for it.
+    pub fn promote_write_in(&mut self, name: &Candidate) -> CandidateOutcome {
+        if self.state == ElectionState::Certified {
+            return CandidateOutcome::Locked(self.state);
+        }
+        let name = self.write_in_name(name);
+        if self.scoreboard.scores.contains_key(&name) {
+            return CandidateOutcome::AlreadyRunning(name);
+        }
+        if !self.scoreboard.write_ins.contains_key(&name) {
+            return CandidateOutcome::UnknownCandidate(name);
+        }
 
-    fn setup() -> VotingMachine {
-        let candidates: Vec<Candidate> = vec![Candidate(String::from("Grahargul le Destructeur de Mondes")), Candidate(String::from("Jean-Marie Bigard"))];
-        let scoreboard = Scoreboard::new(candidates);
-        let voting_machine = VotingMachine::new(scoreboard);
-        voting_machine
+        for scoreboard in [&mut self.scoreboard, &mut self.head_counts] {
+            scoreboard.scores.insert(name.clone(), Score(0));
+        }
+        self.absorb_write_ins(&name, &name);
+        CandidateOutcome::Promoted(name)
     }
 
-    #[test]
-    fn accepted_vote(){
-        let BallotPaper = BallotPaper {
-            voter: Voter(String::from("Claude")),
-            candidate: Some(Candidate(String::from("Grahargul le Destructeur de Mondes"))),
-        };
-        let mut voting_machine = setup();
-                let result = voting_machine.vote(BallotPaper);
-                assert!(matches!(result, VoteOutcome::AcceptedVote(_, _)));
+    /// Counts the votes written in for `name` for the running candidate
+    /// `into`, and rewrites their ballots, secret or not, with the
+    /// candidate's name.
+    fn absorb_write_ins(&mut self, name: &Candidate, into: &Candidate) {
+        for scoreboard in [&mut self.scoreboard, &mut self.head_counts] {
+            let score = scoreboard.write_ins.remove(name).unwrap_or(Score(0));
+            scoreboard.scores.entry(into.clone()).or_insert(Score(0)).0 += score.0;
+        }
+        let aliases = &self.write_in_aliases;
+        let running = &self.scoreboard.scores;
+        let written_for = |ballot: &Ballot| matches!(ballot,
+            Ballot::Single(Some(written)) if !running.contains_key(written) && aliased_write_in(aliases, written) == *name);
+        let rewritten = Ballot::Single(Some(into.clone()));
+        for entry in self.ballots.0.iter_mut().filter(|entry| written_for(&entry.ballot)) {
+            entry.ballot = rewritten.clone();
+        }
+        for ballot in self.cast_ballots.values_mut().filter(|ballot| written_for(ballot)) {
+            *ballot = rewritten.clone();
+        }
+        self.rule.tally(&self.ballots, &mut self.scoreboard);
     }
 
-    #[test]
-    fn blank_vote(){
-        let BallotPaper = BallotPaper {
-            voter: Voter(String::from("Claude")),
-            candidate: None,
-        };
-        let mut voting_machine = setup();
-                let result = voting_machine.vote(BallotPaper);
-                assert!(matches!(result, VoteOutcome::BlankVote(_)));
+    /// Keeps what the voter's counted ballot spends of the budget, for the
+    /// rules that have one.
+    fn record_spending(&mut self, voter: &Voter, ballot: &Ballot, outcome: &VoteOutcome) {
+        if self.rule.budget().is_none() {
+            return;
+        }
+        if counts_for_candidates(outcome) {
+            self.spent_credits.insert(voter.clone(), self.rule.cost(ballot));
+        } else {
+            self.spent_credits.remove(voter);
+        }
     }
 
-    #[test]
-    fn invalid_vote(){
-        let BallotPaper = BallotPaper {
-            voter: Voter(String::from("Claude")),
-            candidate: Some(Candidate(String::from("Ouga Bouga"))),
-        };
-        let mut voting_machine = setup();
-                let result = voting_machine.vote(BallotPaper);
-                assert!(matches!(result, VoteOutcome::InvalidVote(_)));
+    /// Replaces the voter's previous ballot: it is taken off the scoreboard
+    /// and out of the ballot box with the weight the voter carries, then the
+    /// new one is counted in its place.
+    fn revise(&mut self, voter: Voter, previous: Ballot, replacement: Ballot) -> VoteOutcome {
+        let replacement = self.resolve_write_in(replacement);
+        let mut counted = Scoreboard::new(self.scoreboard.scores.keys().cloned().collect());
+        let outcome = self.count(voter.clone(), &replacement, &mut counted);
+        if let VoteOutcome::OverBudget(_, _) = &outcome {
+            return outcome;
+        }
+
+        let carried = self.carried_weights().get(&voter).copied().unwrap_or_else(|| self.voter_weight(&voter));
+        let mut previously_counted = Scoreboard::new(self.scoreboard.scores.keys().cloned().collect());
+        self.count(voter.clone(), &previous, &mut previously_counted);
+        self.scoreboard.subtract_weighted(&previously_counted, carried);
+        self.head_counts.subtract_weighted(&previously_counted, 1);
+        if let Some(position) = self.ballots.0.iter().position(|entry| entry.ballot == previous && entry.weight == carried) {
+            self.ballots.0.remove(position);
+        }
+
+        self.scoreboard.add_weighted(&counted, carried);
+        self.head_counts.add_weighted(&counted, 1);
+        self.record_spending(&voter, &replacement, &outcome);
+        if counts_for_candidates(&outcome) {
+            self.ballots.0.push(WeightedBallot { ballot: replacement.clone(), weight: carried });
+        }
+        self.cast_ballots.insert(voter.clone(), replacement.clone());
+        self.revisions.push(Revision { voter, previous, replacement, at: self.clock.now() });
+        self.rule.tally(&self.ballots, &mut self.scoreboard);
+        outcome
     }
 
-    #[test]
-    fn has_already_voted() {
-        let mut voting_machine = setup();
-        let voter = Voter(String::from("Claude"));
-        
-        let ballot_paper1 = BallotPaper {
-            voter: voter.clone(),
-            candidate: Some(Candidate(String::from("Grahargul le Destructeur de Mondes"))),
-        };
-        let result1 = voting_machine.vote(ballot_paper1);
-        assert!(matches!(result1, VoteOutcome::AcceptedVote(_, _)));
+    /// Adds a candidate while the election is still in draft.
+    pub fn add_candidate(&mut self, candidate: Candidate) -> CandidateOutcome {
+        self.follow_schedule();
+        if self.state != ElectionState::Draft {
+            return CandidateOutcome::Locked(self.state);
+        }
+        if self.scoreboard.scores.contains_key(&candidate) {
+            return CandidateOutcome::AlreadyRunning(candidate);
+        }
+        self.scoreboard.scores.insert(candidate.clone(), Score(0));
+        self.head_counts.scores.insert(candidate.clone(), Score(0));
+        CandidateOutcome::Added(candidate)
+    }
 
-        let ballot_paper2 = BallotPaper {
-            voter: voter.clone(),
-            candidate: Some(Candidate(String::from("Jean-Marie Bigard"))),
+    /// Withdraws a candidate before the election closes, recounting the
+    /// ballots that name them according to `policy`. Every other ballot is
+    /// recounted as well, since positional points depend on the number of
+    /// candidates running, and the credits voters spent follow their
+    /// rewritten ballot. Secret ballots, whose voters are unknown, are
+    /// recounted with the weight they were put in the box with.
+    pub fn withdraw_candidate(&mut self, candidate: Candidate, policy: WithdrawalPolicy) -> CandidateOutcome {
+        self.follow_schedule();
+        if !matches!(self.state, ElectionState::Draft | ElectionState::Open) {
+            return CandidateOutcome::Locked(self.state);
+        }
+        if !self.scoreboard.scores.contains_key(&candidate) {
+            return CandidateOutcome::UnknownCandidate(candidate);
+        }
+
+        let replace = |ballot: &Ballot| match policy {
+            _ if !ballot.mentions(&candidate) => ballot.clone(),
+            WithdrawalPolicy::Invalid => Ballot::Invalid,
+            WithdrawalPolicy::Blank => ballot.blank(),
+            WithdrawalPolicy::Transfer => ballot.without(&candidate),
+        };
+        let carried = self.carried_weights();
+        let recounted: Vec<(Voter, Ballot, Ballot, usize)> = if self.secret_ballot {
+            self.ballots.0.iter()
+                .map(|entry| (Voter(String::new()), entry.ballot.clone(), replace(&entry.ballot), entry.weight))
+                .collect()
+        } else {
+            self.cast_ballots.iter()
+                .map(|(voter, ballot)| (voter.clone(), ballot.clone(), replace(ballot), carried.get(voter).copied().unwrap_or(0)))
+                .collect()
         };
-        let result2 = voting_machine.vote(ballot_paper2);
-        assert!(matches!(result2, VoteOutcome::HasAlreadyVoted(_)));
 
-        let scoreboard = voting_machine.get_scoreboard();
-        assert_eq!(scoreboard.scores.get(&Candidate(String::from("Grahargul le Destructeur de Mondes"))).unwrap().0, 1);
-        assert_eq!(scoreboard.scores.get(&Candidate(String::from("Jean-Marie Bigard"))).unwrap().0, 0);
+        let running: Vec<Candidate> = self.scoreboard.scores.keys().cloned().collect();
+        for (voter, previous, _, weight) in &recounted {
+            let mut counted = Scoreboard::new(running.clone());
+            self.count(voter.clone(), previous, &mut counted);
+            self.scoreboard.subtract_weighted(&counted, *weight);
+            self.head_counts.subtract_weighted(&counted, 1);
+        }
+        self.scoreboard.scores.remove(&candidate);
+        self.head_counts.scores.remove(&candidate);
+        let running: Vec<Candidate> = self.scoreboard.scores.keys().cloned().collect();
+        for (voter, _, replacement, weight) in &recounted {
+            let mut counted = Scoreboard::new(running.clone());
+            let outcome = self.count(voter.clone(), replacement, &mut counted);
+            self.scoreboard.add_weighted(&counted, *weight);
+            self.head_counts.add_weighted(&counted, 1);
+            if !self.secret_ballot {
+                self.cast_ballots.insert(voter.clone(), replacement.clone());
+                self.record_spending(voter, replacement, &outcome);
+            }
+        }
+
+        self.ballots.0 = std::mem::take(&mut self.ballots.0).into_iter()
+            .filter_map(|entry| match entry.ballot.mentions(&candidate) {
+                true if policy == WithdrawalPolicy::Invalid => None,
+                true => Some(WeightedBallot { ballot: replace(&entry.ballot), ..entry })
+                    .filter(|replaced| replaced.ballot != replaced.ballot.blank()),
+                false => Some(entry),
+            })
+            .collect();
+        self.rule.tally(&self.ballots, &mut self.scoreboard);
+        CandidateOutcome::Withdrawn(candidate)
+    }
+
+    /// Corrects a candidate's name everywhere it appears, archived rounds,
+    /// revisions and write-in merges included, until the results are
+    /// certified.
+    pub fn rename_candidate(&mut self, from: Candidate, to: Candidate) -> CandidateOutcome {
+        self.follow_schedule();
+        if self.state == ElectionState::Certified {
+            return CandidateOutcome::Locked(self.state);
+        }
+        if !self.scoreboard.scores.contains_key(&from) {
+            return CandidateOutcome::UnknownCandidate(from);
+        }
+        if self.scoreboard.scores.contains_key(&to) {
+            return CandidateOutcome::AlreadyRunning(to);
+        }
+        let written_in = self.write_in_name(&to);
+        if self.scoreboard.write_ins.contains_key(&written_in) {
+            return CandidateOutcome::WrittenIn(written_in);
+        }
+
+        let archived = self.archived_rounds.iter_mut();
+        let scoreboards = [&mut self.scoreboard, &mut self.head_counts].into_iter()
+            .chain(archived.flat_map(|round| [&mut round.scoreboard, &mut round.head_counts]));
+        for scoreboard in scoreboards {
+            if let Some(score) = scoreboard.scores.remove(&from) {
+                scoreboard.scores.insert(to.clone(), score);
+            }
+        }
+        let ballots = self.ballots.0.iter_mut()
+            .chain(self.archived_rounds.iter_mut().flat_map(|round| round.ballots.0.iter_mut()))
+            .map(|entry| &mut entry.ballot)
+            .chain(self.cast_ballots.values_mut())
+            .chain(self.revisions.iter_mut().flat_map(|revision| [&mut revision.previous, &mut revision.replacement]));
+        for ballot in ballots {
+            *ballot = ballot.renamed(&from, &to);
+        }
+        for target in self.write_in_aliases.values_mut().filter(|target| **target == from) {
+            *target = to.clone();
+        }
+        self.rule.tally(&self.ballots, &mut self.scoreboard);
+        CandidateOutcome::Renamed(from, to)
     }
-}
\ No newline at end of file
+
+    /// Lets `holder` vote on behalf of `principal`, within the number of
+    /// proxies a holder may carry.
+    pub fn grant_proxy(&mut self, principal: Voter, holder: Voter) -> VoteOutcome {
+        if let Some(refusal) = self.refuse_outside_opening(&principal) {
+            return refusal;
+        }
+        for voter in [&principal, &holder] {
+            if !self.is_eligible(voter) {
+                return VoteOutcome::NotEligible(voter.clone());
+            }
+        }
+        if self.voters.0.contains(&principal) {
+            return VoteOutcome::HasAlreadyVoted(principal);
+        }
+        if principal == holder {
+            return VoteOutcome::ProxyNotGranted(principal, holder);
+        }
+        let held = self.proxies.iter()
+            .filter(|(other, other_holder)| **other_holder == holder && **other != principal)
+            .count();
+        if held >= self.max_proxies {
+            return VoteOutcome::ProxyLimitExceeded(holder);
+        }
+        self.proxies.insert(principal.clone(), holder.clone());
+        VoteOutcome::ProxyAccepted(principal, holder)
+    }
+
+    /// Casts the principal's ballot, named on the ballot paper, on behalf of
+    /// the proxy holder.
+    pub fn vote_by_proxy(&mut self, holder: Voter, ballot_paper: BallotPaper) -> VoteOutcome {
+        let principal = ballot_paper.voter.clone();
+        if self.proxies.get(&principal) != Some(&holder) {
+            return VoteOutcome::ProxyNotGranted(principal, holder);
+        }
+        let outcome = self.vote(ballot_paper);
+        if self.voters.0.contains(&principal) {
+            // Voting removed the proxy as for an in-person vote; it still
+            // counts towards the holder's limit.
+            self.proxies.insert(principal.clone(), holder.clone());
+            self.proxy_votes.insert(principal, holder);
+        }
+        outcome
+    }
+
+    pub fn get_proxies(&self) -> &Map<Voter, Voter> {
+        &self.proxies
+    }
+
+    pub fn get_max_proxies(&self) -> usize {
+        self.max_proxies
+    }
+
+    pub fn get_proxy_votes(&self) -> &Map<Voter, Voter> {
+        &self.proxy_votes
+    }
+
+    fn delegate(&mut self, delegator: Voter, delegate: Voter) -> VoteOutcome {
+        if creates_cycle(&self.delegations, &delegator, &delegate) {
+            return VoteOutcome::DelegationCycle(delegator, delegate);
+        }
+
+        let carried_before = self.carried_weights();
+        self.voters.0.insert(delegator.clone());
+        self.use_code(&delegator);
+        self.delegations.insert(delegator.clone(), delegate.clone());
+        self.reweigh(carried_before);
+        VoteOutcome::Delegated(delegator, delegate)
+    }
+
+    /// Brings the scoreboard and the ballot box in line with the weight each
+    /// direct voter now carries, recounting the ballots whose weight changed.
+    fn reweigh(&mut self, carried_before: Map<Voter, usize>) {
+        for (voter, carried) in self.carried_weights() {
+            let previous = carried_before.get(&voter).copied().unwrap_or(0);
+            let Some(ballot) = self.cast_ballots.get(&voter) else {
+                continue;
+            };
+            let mut counted = Scoreboard::new(self.scoreboard.scores.keys().cloned().collect());
+            self.count(voter.clone(), ballot, &mut counted);
+            if carried > previous {
+                self.scoreboard.add_weighted(&counted, carried - previous);
+            } else if carried < previous {
+                self.scoreboard.subtract_weighted(&counted, previous - carried);
+            }
+            // Voters who just voted are put in the box by `vote`.
+            if carried != previous && carried_before.contains_key(&voter) {
+                if let Some(entry) = self.ballots.0.iter_mut().find(|entry| entry.ballot == *ballot && entry.weight == previous) {
+                    entry.weight = carried;
+                }
+            }
+        }
+    }
+
+    /// Weight carried by each direct voter, their own included, once every
+    /// delegation chain is resolved.
+    pub fn carried_weights(&self) -> Map<Voter, usize> {
+        let direct_voters: Set<&Voter> = self.cast_ballots.keys().collect();
+        carried_weights(&self.delegations, &direct_voters, |voter| self.voter_weight(voter))
+    }
+
+    pub fn get_delegations(&self) -> &Map<Voter, Voter> {
+        &self.delegations
+    }
+
+    pub fn get_cast_ballots(&self) -> &Map<Voter, Ballot> {
+        &self.cast_ballots
+    }
+
+    pub fn get_scoreboard(&self) -> &Scoreboard {
+        &self.scoreboard
+    }
+
+    /// Totals counting one per ballot, whatever the voters' weights.
+    pub fn get_head_counts(&self) -> &Scoreboard {
+        &self.head_counts
+    }
+
+    pub fn get_voter_weights(&self) -> &Map<Voter, usize> {
+        &self.voter_weights
+    }
+
+    pub fn voter_weight(&self, voter: &Voter) -> usize {
+        self.voter_weights.get(voter).copied().unwrap_or(1)
+    }
+
+    pub fn get_voters(&self) -> &AttendanceSheet {
+        &self.voters
+    }
+
+    pub fn get_ballots(&self) -> &BallotBox {
+        &self.ballots
+    }
+
+    pub fn get_rule(&self) -> &dyn TallyRule {
+        self.rule.as_ref()
+    }
+
+    /// The counting rule, when it is a `Rule`.
+    pub fn rule_as<Rule: TallyRule>(&self) -> Option<&Rule> {
+        let rule: &dyn Any = self.rule.as_ref();
+        rule.downcast_ref()
+    }
+
+    /// Closes the current plurality round. A candidate with an absolute
+    /// majority of the expressed votes is elected; otherwise, during the first
+    /// round of a two-round election, the round is archived and a second one
+    /// opens between the two leading candidates with a fresh attendance sheet:
+    /// codes, proxies and credits can be used again. Any other round is won
+    /// by the candidate with the most votes.
+    pub fn close_round(&mut self) -> RoundOutcome {
+        let ranking = self.scoreboard.ranking();
+        let expressed: usize = ranking.iter().map(|(_, score)| score.0).sum();
+        let leader = ranking.first().map(|(candidate, score)| ((*candidate).clone(), score.0));
+        let runner_up = ranking.get(1).map(|(_, score)| score.0);
+
+        match leader {
+            Some((candidate, votes)) if votes * 2 > expressed => RoundOutcome::Elected(candidate),
+            Some(_) if self.rule.has_second_round() && self.archived_rounds.is_empty() => {
+                let finalists: Vec<Candidate> = ranking.iter().take(2).map(|(candidate, _)| (*candidate).clone()).collect();
+                self.archived_rounds.push(ArchivedRound {
+                    voters: std::mem::replace(&mut self.voters, AttendanceSheet(Set::new())),
+                    scoreboard: std::mem::replace(&mut self.scoreboard, Scoreboard::new(finalists.clone())),
+                    head_counts: std::mem::replace(&mut self.head_counts, Scoreboard::new(finalists.clone())),
+                    ballots: std::mem::replace(&mut self.ballots, BallotBox(Vec::new())),
+                });
+                self.delegations.clear();
+                self.cast_ballots.clear();
+                self.used_codes.clear();
+                self.proxies.clear();
+                self.proxy_votes.clear();
+                self.spent_credits.clear();
+                RoundOutcome::SecondRound(finalists)
+            }
+            Some((candidate, votes)) if runner_up != Some(votes) => RoundOutcome::Elected(candidate),
+            _ => RoundOutcome::Undecided,
+        }
+    }
+
+    /// Refuses the voter unless the election is open, once it has followed
+    /// its schedule. No vote is taken before the opening time, even on an
+    /// election opened by hand.
+    fn refuse_outside_opening(&mut self, voter: &Voter) -> Option<VoteOutcome> {
+        self.follow_schedule();
+        let now = self.clock.now();
+        match (self.state, self.opens_at, self.closes_at) {
+            (_, Some(opens_at), _) if now < opens_at => Some(VoteOutcome::NotYetOpen(voter.clone(), opens_at)),
+            (ElectionState::Open, _, _) => None,
+            (ElectionState::Closed | ElectionState::Certified, _, Some(closes_at)) if now >= closes_at => {
+                Some(VoteOutcome::AlreadyClosed(voter.clone(), closes_at))
+            }
+            (state, _, _) => Some(VoteOutcome::ElectionNotOpen(voter.clone(), state)),
+        }
+    }
+
+    /// Opens a draft election once its opening time has come and closes an
+    /// open one once its closing time has passed.
+    pub fn follow_schedule(&mut self) {
+        let now = self.clock.now();
+        if self.state == ElectionState::Draft && self.opens_at.is_some_and(|opens_at| now >= opens_at) {
+            self.state = ElectionState::Open;
+        }
+        if self.state == ElectionState::Open && self.closes_at.is_some_and(|closes_at| now >= closes_at) {
+            self.state = ElectionState::Closed;
+        }
+    }
+
+    /// Refuses a ballot paper whose code does not match the voter's, once
+    /// codes were issued. A voter who delegated their vote may still vote
+    /// directly with the code they delegated with, and one who may revise
+    /// their ballot reuses their code.
+    fn check_code(&self, voter: &Voter, code: Option<&VotingCode>) -> Option<VoteOutcome> {
+        if self.code_hashes.is_empty() {
+            return None;
+        }
+        let expected = self.code_hashes.get(voter);
+        if code.is_none_or(|code| expected != Some(&hash_code(code))) {
+            return Some(VoteOutcome::InvalidCode(voter.clone()));
+        }
+        if self.used_codes.contains(voter) && !self.delegations.contains_key(voter) && !self.allow_revision {
+            return Some(VoteOutcome::CodeAlreadyUsed(voter.clone()));
+        }
+        None
+    }
+
+    fn use_code(&mut self, voter: &Voter) {
+        if self.code_hashes.contains_key(voter) {
+            self.used_codes.insert(voter.clone());
+        }
+    }
+
+    /// Draws a fresh code for every voter on the roll and keeps only their
+    /// hashes, so the returned codes cannot be printed again. Codes are
+    /// issued while the election is a draft, before any of them is used.
+    pub fn issue_codes(&mut self) -> CodesOutcome {
+        self.follow_schedule();
+        if self.state != ElectionState::Draft {
+            return CodesOutcome::Locked(self.state);
+        }
+        let Some(roll) = self.roll.as_ref() else {
+            return CodesOutcome::NoRoll;
+        };
+        let codes: Map<Voter, VotingCode> = roll.0.iter().map(|voter| (voter.clone(), generate_code())).collect();
+        self.code_hashes = codes.iter().map(|(voter, code)| (voter.clone(), hash_code(code))).collect();
+        CodesOutcome::Issued(codes)
+    }
+
+    /// Whether ballot papers must carry the voter's code.
+    pub fn requires_codes(&self) -> bool {
+        !self.code_hashes.is_empty()
+    }
+
+    pub fn get_code_hashes(&self) -> &Map<Voter, CodeHash> {
+        &self.code_hashes
+    }
+
+    pub fn get_used_codes(&self) -> &Set<Voter> {
+        &self.used_codes
+    }
+
+    /// Whether the voter is on the electoral roll, when there is one.
+    pub fn is_eligible(&self, voter: &Voter) -> bool {
+        self.roll.as_ref().is_none_or(|roll| roll.0.contains(voter))
+    }
+
+    pub fn get_roll(&self) -> Option<&ElectoralRoll> {
+        self.roll.as_ref()
+    }
+
+    /// Registered voters on the attendance sheet against the size of the
+    /// roll, when there is one.
+    pub fn turnout(&self) -> Option<Turnout> {
+        let roll = self.roll.as_ref()?;
+        Some(Turnout {
+            voted: self.voters.0.iter().filter(|voter| roll.0.contains(voter)).count(),
+            registered: roll.0.len(),
+        })
+    }
+
+    pub fn get_state(&self) -> ElectionState {
+        self.state
+    }
+
+    pub fn get_opening_time(&self) -> Option<Timestamp> {
+        self.opens_at
+    }
+
+    pub fn get_closing_time(&self) -> Option<Timestamp> {
+        self.closes_at
+    }
+
+    /// Moves the election to `state`, one step at a time along draft, open,
+    /// closed and certified.
+    pub fn transition(&mut self, state: ElectionState) -> TransitionOutcome {
+        self.follow_schedule();
+        let allowed = matches!(
+            (self.state, state),
+            (ElectionState::Draft, ElectionState::Open)
+                | (ElectionState::Open, ElectionState::Closed)
+                | (ElectionState::Closed, ElectionState::Certified)
+        );
+        if !allowed {
+            return TransitionOutcome::Refused(self.state, state);
+        }
+        let previous = std::mem::replace(&mut self.state, state);
+        TransitionOutcome::Changed(previous, state)
+    }
+
+    pub fn hides_results(&self) -> bool {
+        self.hide_results
+    }
+
+    /// Whether the results may be shown, which they always may once the
+    /// election is closed.
+    pub fn results_visible(&self) -> bool {
+        !self.hide_results || matches!(self.state, ElectionState::Closed | ElectionState::Certified)
+    }
+
+    pub fn get_spent_credits(&self) -> &Map<Voter, usize> {
+        &self.spent_credits
+    }
+
+    /// Points or credits the voter has left, when the rule gives a budget.
+    /// Secret ballots report none: what a voter spent would tell part of
+    /// their ballot.
+    pub fn remaining_credits(&self, voter: &Voter) -> Option<usize> {
+        if self.secret_ballot {
+            return None;
+        }
+        let budget = self.rule.budget()?;
+        let spent = self.spent_credits.get(voter).copied().unwrap_or(0);
+        Some(budget.saturating_sub(spent))
+    }
+
+    pub fn get_archived_rounds(&self) -> &[ArchivedRound] {
+        &self.archived_rounds
+    }
+
+    /// Results the counting rule computes from the ballot box, beyond the
+    /// scoreboard.
+    pub fn report(&self) -> Option<TallyReport> {
+        self.rule.report(&self.scoreboard, &self.ballots)
+    }
+
+    /// Multi-seat count of the ballot box, when the machine runs a single
+    /// transferable vote election.
+    pub fn transferable_vote_result(&self) -> Option<TransferableVoteResult> {
+        match self.report()? {
+            TallyReport::TransferableVote(result) => Some(result),
+            _ => None,
+        }
+    }
+
+    /// Automatic runoff between the two best-scored candidates, when the
+    /// machine runs a STAR election.
+    pub fn star_result(&self) -> Option<StarResult> {
+        match self.report()? {
+            TallyReport::Star(result) => Some(result),
+            _ => None,
+        }
+    }
+
+    /// Candidates ranked by majority grade, when the machine runs a majority
+    /// judgment election.
+    pub fn majority_judgment_result(&self) -> Option<Vec<JudgedCandidate>> {
+        match self.report()? {
+            TallyReport::MajorityJudgment(result) => Some(result),
+            _ => None,
+        }
+    }
+
+    /// Votes and credits spent per candidate, when the machine runs a
+    /// quadratic vote.
+    pub fn quadratic_result(&self) -> Option<QuadraticResult> {
+        match self.report()? {
+            TallyReport::Quadratic(result) => Some(result),
+            _ => None,
+        }
+    }
+
+    pub fn pairwise_preferences(&self) -> PairwiseMatrix {
+        let candidates: Vec<Candidate> = self.scoreboard.scores.keys().cloned().collect();
+        pairwise_preferences(&candidates, &self.ballots)
+    }
+
+    pub fn recover_from(voters: AttendanceSheet, scoreboard: Scoreboard) -> Self {
+        Self {voters, ..Self::new(scoreboard)}
+    }
+}
+
+/// Whether a counted ballot went to someone, and so belongs in the ballot
+/// box. Blank ballots stay out of it: they are counted on the scoreboard
+/// only. Write-ins go in, for their votes to follow a promotion; the
+/// tallies ignore the names that are not running.
+fn counts_for_candidates(outcome: &VoteOutcome) -> bool {
+    !matches!(outcome, VoteOutcome::BlankVote(_) | VoteOutcome::InvalidVote(_))
+}
+
+/// Normalized spelling `name` is counted under as a write-in, following
+/// the merges recorded in `aliases`.
+fn aliased_write_in(aliases: &Map<Candidate, Candidate>, name: &Candidate) -> Candidate {
+    let name = normalize_write_in(&name.0);
+    aliases.get(&name).cloned().unwrap_or(name)
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use crate::tallies::approval::Approval;
+    use crate::tallies::cumulative::Cumulative;
+    use crate::tallies::instant_runoff::InstantRunoff;
+    use crate::tallies::majority_judgment::{GradeScale, MajorityJudgment};
+    use crate::tallies::plurality::TwoRound;
+    use crate::tallies::positional::{Positional, PositionalRule};
+    use crate::tallies::quadratic::Quadratic;
+
+
+    fn setup() -> VotingMachine {
+        let candidates: Vec<Candidate> = vec![Candidate(String::from("Grahargul le Destructeur de Mondes")), Candidate(String::from("Jean-Marie Bigard"))];
+        let scoreboard = Scoreboard::new(candidates);
+        VotingMachine::new(scoreboard)
+    }
+
+    fn candidate(name: &str) -> Candidate {
+        Candidate(String::from(name))
+    }
+
+    fn ballot_paper(voter: &str, ballot: Ballot) -> BallotPaper {
+        BallotPaper {
+            voter: Voter(String::from(voter)),
+            ballot,
+            code: None,
+        }
+    }
+
+    #[test]
+    fn accepted_vote(){
+        let ballot_paper = BallotPaper {
+            voter: Voter(String::from("Claude")),
+            ballot: Ballot::Single(Some(Candidate(String::from("Grahargul le Destructeur de Mondes")))),
+            code: None,
+        };
+        let mut voting_machine = setup();
+                let result = voting_machine.vote(ballot_paper);
+                assert!(matches!(result, VoteOutcome::AcceptedVote(_, _)));
+    }
+
+    #[test]
+    fn blank_vote(){
+        let ballot_paper = BallotPaper {
+            voter: Voter(String::from("Claude")),
+            ballot: Ballot::Single(None),
+            code: None,
+        };
+        let mut voting_machine = setup();
+        let result = voting_machine.vote(ballot_paper);
+        assert!(matches!(result, VoteOutcome::BlankVote(_)));
+        assert!(voting_machine.get_ballots().0.is_empty());
+    }
+
+    #[test]
+    fn invalid_vote(){
+        let ballot_paper = BallotPaper {
+            voter: Voter(String::from("Claude")),
+            ballot: Ballot::Single(Some(Candidate(String::from("Ouga Bouga")))),
+            code: None,
+        };
+        let mut voting_machine = setup();
+        let result = voting_machine.vote(ballot_paper);
+        assert!(matches!(result, VoteOutcome::InvalidVote(_)));
+        assert!(voting_machine.get_ballots().0.is_empty());
+    }
+
+    #[test]
+    fn majority_judgment_result_reads_the_ballot_box(){
+        let scale = GradeScale(vec![Grade(String::from("Excellent")), Grade(String::from("À rejeter"))]);
+        let mut voting_machine = setup().with_rule(Arc::new(MajorityJudgment { scale }));
+        voting_machine.vote(ballot_paper("Claude", Ballot::Graded(Map::from([(candidate("Jean-Marie Bigard"), Grade(String::from("Excellent")))]))));
+
+        let result = voting_machine.majority_judgment_result().unwrap();
+        assert_eq!(result[0].candidate, candidate("Jean-Marie Bigard"));
+        assert_eq!(result[0].majority_grade, Some(Grade(String::from("Excellent"))));
+        assert_eq!(result[0].distribution, vec![1, 0]);
+    }
+
+    #[test]
+    fn has_already_voted() {
+        let mut voting_machine = setup();
+        let voter = Voter(String::from("Claude"));
+        
+        let ballot_paper1 = BallotPaper {
+            voter: voter.clone(),
+            ballot: Ballot::Single(Some(Candidate(String::from("Grahargul le Destructeur de Mondes")))),
+            code: None,
+        };
+        let result1 = voting_machine.vote(ballot_paper1);
+        assert!(matches!(result1, VoteOutcome::AcceptedVote(_, _)));
+
+        let ballot_paper2 = BallotPaper {
+            voter: voter.clone(),
+            ballot: Ballot::Single(Some(Candidate(String::from("Jean-Marie Bigard")))),
+            code: None,
+        };
+        let result2 = voting_machine.vote(ballot_paper2);
+        assert!(matches!(result2, VoteOutcome::HasAlreadyVoted(_)));
+
+        let scoreboard = voting_machine.get_scoreboard();
+        assert_eq!(scoreboard.scores.get(&Candidate(String::from("Grahargul le Destructeur de Mondes"))).unwrap().0, 1);
+        assert_eq!(scoreboard.scores.get(&Candidate(String::from("Jean-Marie Bigard"))).unwrap().0, 0);
+    }
+
+    #[test]
+    fn absolute_majority_in_first_round(){
+        let mut voting_machine = setup().with_rule(Arc::new(TwoRound));
+        voting_machine.vote(ballot_paper("Claude", Ballot::Single(Some(candidate("Jean-Marie Bigard")))));
+        voting_machine.vote(ballot_paper("Alice", Ballot::Single(Some(candidate("Jean-Marie Bigard")))));
+        voting_machine.vote(ballot_paper("Bob", Ballot::Single(Some(candidate("Grahargul le Destructeur de Mondes")))));
+
+        let outcome = voting_machine.close_round();
+        assert!(matches!(outcome, RoundOutcome::Elected(candidate) if candidate.0 == "Jean-Marie Bigard"));
+        assert!(voting_machine.get_archived_rounds().is_empty());
+    }
+
+    #[test]
+    fn second_round_between_top_two(){
+        let candidates = vec![Candidate(String::from("Alice")), Candidate(String::from("Bob")), Candidate(String::from("Charlie"))];
+        let mut voting_machine = VotingMachine::new(Scoreboard::new(candidates)).with_rule(Arc::new(TwoRound));
+        voting_machine.vote(ballot_paper("Claude", Ballot::Single(Some(candidate("Alice")))));
+        voting_machine.vote(ballot_paper("Jane", Ballot::Single(Some(candidate("Bob")))));
+        voting_machine.vote(ballot_paper("John", Ballot::Single(Some(candidate("Charlie")))));
+        voting_machine.vote(ballot_paper("Jim", Ballot::Single(Some(candidate("Alice")))));
+
+        let outcome = voting_machine.close_round();
+        assert!(matches!(&outcome, RoundOutcome::SecondRound(finalists) if finalists.len() == 2 && finalists[0].0 == "Alice"));
+        assert_eq!(voting_machine.get_archived_rounds().len(), 1);
+        assert_eq!(voting_machine.get_archived_rounds()[0].voters.0.len(), 4);
+        assert!(voting_machine.get_voters().0.is_empty());
+        assert!(!voting_machine.get_scoreboard().scores.contains_key(&Candidate(String::from("Charlie"))));
+
+        let result = voting_machine.vote(ballot_paper("Claude", Ballot::Single(Some(candidate("Charlie")))));
+        assert!(matches!(result, VoteOutcome::InvalidVote(_)));
+        voting_machine.vote(ballot_paper("Jane", Ballot::Single(Some(candidate("Bob")))));
+        voting_machine.vote(ballot_paper("John", Ballot::Single(Some(candidate("Bob")))));
+        let outcome = voting_machine.close_round();
+        assert!(matches!(outcome, RoundOutcome::Elected(candidate) if candidate.0 == "Bob"));
+    }
+
+    #[test]
+    fn codes_serve_again_in_the_second_round(){
+        let candidates = vec![Candidate(String::from("Alice")), Candidate(String::from("Bob")), Candidate(String::from("Charlie"))];
+        let roll = ElectoralRoll(["Claude", "Jane", "John"].map(|name| Voter(String::from(name))).into());
+        let mut voting_machine = VotingMachine::new(Scoreboard::new(candidates))
+            .with_rule(Arc::new(TwoRound))
+            .with_state(ElectionState::Draft)
+            .with_roll(Some(roll));
+        let CodesOutcome::Issued(codes) = voting_machine.issue_codes() else { panic!("codes should be issued") };
+        voting_machine.transition(ElectionState::Open);
+        let with_code = |voter: &str, name: &str| BallotPaper {
+            code: codes.get(&Voter(String::from(voter))).cloned(),
+            ..ballot_paper(voter, Ballot::Single(Some(candidate(name))))
+        };
+        voting_machine.vote(with_code("Claude", "Alice"));
+        voting_machine.vote(with_code("Jane", "Bob"));
+        voting_machine.vote(with_code("John", "Charlie"));
+
+        assert!(matches!(voting_machine.close_round(), RoundOutcome::SecondRound(_)));
+        assert!(voting_machine.get_used_codes().is_empty());
+        assert!(matches!(voting_machine.vote(with_code("Claude", "Alice")), VoteOutcome::AcceptedVote(_, _)));
+        assert!(matches!(voting_machine.vote(with_code("Claude", "Alice")), VoteOutcome::CodeAlreadyUsed(_)));
+    }
+
+    #[test]
+    fn over_budget_ballot_can_be_cast_again(){
+        let mut voting_machine = setup().with_rule(Arc::new(Cumulative { budget: 3 }));
+        let rejected = voting_machine.vote(ballot_paper("Claude", Ballot::Scored(Map::from([(candidate("Jean-Marie Bigard"), 3), (candidate("Grahargul le Destructeur de Mondes"), 1)]))));
+        assert!(matches!(rejected, VoteOutcome::OverBudget(_, 4)));
+        assert!(voting_machine.get_voters().0.is_empty());
+        assert!(voting_machine.get_ballots().0.is_empty());
+
+        let accepted = voting_machine.vote(ballot_paper("Claude", Ballot::Scored(Map::from([(candidate("Jean-Marie Bigard"), 3)]))));
+        assert!(matches!(accepted, VoteOutcome::AcceptedScores(_, _)));
+        assert_eq!(voting_machine.get_scoreboard().scores.get(&Candidate(String::from("Jean-Marie Bigard"))).unwrap().0, 3);
+    }
+
+    #[test]
+    fn remaining_quadratic_credits(){
+        let mut voting_machine = setup().with_rule(Arc::new(Quadratic { credits: 10 }));
+        voting_machine.vote(ballot_paper("Claude", Ballot::Scored(Map::from([(candidate("Jean-Marie Bigard"), 2), (candidate("Grahargul le Destructeur de Mondes"), 1)]))));
+
+        assert_eq!(voting_machine.remaining_credits(&Voter(String::from("Claude"))), Some(5));
+        assert_eq!(voting_machine.remaining_credits(&Voter(String::from("Alice"))), Some(10));
+        let result = voting_machine.quadratic_result().unwrap();
+        assert_eq!(result.credits.get(&Candidate(String::from("Jean-Marie Bigard"))), Some(&4));
+    }
+
+    #[test]
+    fn withdrawal_refunds_the_credits_spent_on_the_candidate(){
+        let claude = Voter(String::from("Claude"));
+        let mut voting_machine = setup().with_rule(Arc::new(Quadratic { credits: 10 }));
+        voting_machine.vote(ballot_paper("Claude", Ballot::Scored(Map::from([(candidate("Jean-Marie Bigard"), 2), (candidate("Grahargul le Destructeur de Mondes"), 1)]))));
+        voting_machine.vote(ballot_paper("Alice", Ballot::Scored(Map::from([(candidate("Jean-Marie Bigard"), 3)]))));
+
+        voting_machine.withdraw_candidate(Candidate(String::from("Jean-Marie Bigard")), WithdrawalPolicy::Transfer);
+
+        assert_eq!(voting_machine.remaining_credits(&claude), Some(9));
+        assert_eq!(voting_machine.remaining_credits(&Voter(String::from("Alice"))), Some(10));
+    }
+
+    #[test]
+    fn secret_ballots_report_no_credits(){
+        let mut voting_machine = setup().with_rule(Arc::new(Quadratic { credits: 10 })).with_secret_ballot(true);
+        voting_machine.vote(ballot_paper("Claude", Ballot::Scored(Map::from([(candidate("Jean-Marie Bigard"), 2)]))));
+
+        assert_eq!(voting_machine.remaining_credits(&Voter(String::from("Claude"))), None);
+        assert!(voting_machine.get_spent_credits().is_empty());
+    }
+
+    #[test]
+    fn weighted_voters(){
+        let weights = Map::from([(Voter(String::from("Claude")), 120), (Voter(String::from("Alice")), 30)]);
+        let mut voting_machine = setup().with_voter_weights(weights);
+        voting_machine.vote(ballot_paper("Claude", Ballot::Single(Some(candidate("Jean-Marie Bigard")))));
+        voting_machine.vote(ballot_paper("Alice", Ballot::Single(Some(candidate("Grahargul le Destructeur de Mondes")))));
+        voting_machine.vote(ballot_paper("Bob", Ballot::Single(Some(candidate("Grahargul le Destructeur de Mondes")))));
+
+        let bigard = Candidate(String::from("Jean-Marie Bigard"));
+        let grahargul = Candidate(String::from("Grahargul le Destructeur de Mondes"));
+        assert_eq!(voting_machine.get_scoreboard().scores.get(&bigard).unwrap().0, 120);
+        assert_eq!(voting_machine.get_scoreboard().scores.get(&grahargul).unwrap().0, 31);
+        assert_eq!(voting_machine.get_head_counts().scores.get(&bigard).unwrap().0, 1);
+        assert_eq!(voting_machine.get_head_counts().scores.get(&grahargul).unwrap().0, 2);
+        assert!(matches!(voting_machine.close_round(), RoundOutcome::Elected(candidate) if candidate == bigard));
+    }
+
+    #[test]
+    fn delegated_votes_follow_the_chain(){
+        let mut voting_machine = setup();
+        let bigard = Candidate(String::from("Jean-Marie Bigard"));
+        assert!(matches!(voting_machine.vote(ballot_paper("Alice", Ballot::Delegated(Voter(String::from("Bob"))))), VoteOutcome::Delegated(_, _)));
+        voting_machine.vote(ballot_paper("Bob", Ballot::Delegated(Voter(String::from("Claude")))));
+        voting_machine.vote(ballot_paper("Claude", Ballot::Single(Some(candidate("Jean-Marie Bigard")))));
+        assert_eq!(voting_machine.get_scoreboard().scores.get(&bigard).unwrap().0, 3);
+        assert_eq!(voting_machine.carried_weights().get(&Voter(String::from("Claude"))), Some(&3));
+
+        voting_machine.vote(ballot_paper("Dave", Ballot::Delegated(Voter(String::from("Claude")))));
+        assert_eq!(voting_machine.get_scoreboard().scores.get(&bigard).unwrap().0, 4);
+        assert_eq!(voting_machine.get_head_counts().scores.get(&bigard).unwrap().0, 1);
+    }
+
+    #[test]
+    fn direct_vote_overrides_delegation(){
+        let mut voting_machine = setup();
+        let bigard = Candidate(String::from("Jean-Marie Bigard"));
+        let grahargul = Candidate(String::from("Grahargul le Destructeur de Mondes"));
+        voting_machine.vote(ballot_paper("Alice", Ballot::Delegated(Voter(String::from("Claude")))));
+        voting_machine.vote(ballot_paper("Claude", Ballot::Single(Some(candidate("Jean-Marie Bigard")))));
+
+        let result = voting_machine.vote(ballot_paper("Alice", Ballot::Single(Some(candidate("Grahargul le Destructeur de Mondes")))));
+        assert!(matches!(result, VoteOutcome::AcceptedVote(_, _)));
+        assert_eq!(voting_machine.get_scoreboard().scores.get(&bigard).unwrap().0, 1);
+        assert_eq!(voting_machine.get_scoreboard().scores.get(&grahargul).unwrap().0, 1);
+        assert!(matches!(voting_machine.vote(ballot_paper("Alice", Ballot::Single(Some(candidate("Jean-Marie Bigard"))))), VoteOutcome::HasAlreadyVoted(_)));
+    }
+
+    #[test]
+    fn delegated_weight_reaches_the_ballot_box(){
+        let mut voting_machine = setup().with_rule(Arc::new(InstantRunoff));
+        let grahargul = Candidate(String::from("Grahargul le Destructeur de Mondes"));
+        voting_machine.vote(ballot_paper("Alice", Ballot::Delegated(Voter(String::from("Xavier")))));
+        voting_machine.vote(ballot_paper("Jane", Ballot::Ranked(vec![candidate("Jean-Marie Bigard")])));
+        voting_machine.vote(ballot_paper("Xavier", Ballot::Ranked(vec![candidate("Grahargul le Destructeur de Mondes")])));
+        voting_machine.vote(ballot_paper("Bob", Ballot::Delegated(Voter(String::from("Xavier")))));
+        voting_machine.vote(ballot_paper("John", Ballot::Ranked(vec![candidate("Jean-Marie Bigard")])));
+
+        assert_eq!(voting_machine.get_scoreboard().scores.get(&grahargul).unwrap().0, 3);
+        let weights: Vec<usize> = voting_machine.get_ballots().0.iter().map(|entry| entry.weight).collect();
+        assert_eq!(weights, vec![1, 3, 1]);
+        assert_eq!(voting_machine.get_scoreboard().runoff_winner(), Some(&grahargul));
+    }
+
+    #[test]
+    fn delegation_cycles_are_refused(){
+        let mut voting_machine = setup();
+        voting_machine.vote(ballot_paper("Alice", Ballot::Delegated(Voter(String::from("Bob")))));
+
+        assert!(matches!(voting_machine.vote(ballot_paper("Bob", Ballot::Delegated(Voter(String::from("Alice"))))), VoteOutcome::DelegationCycle(_, _)));
+        assert!(matches!(voting_machine.vote(ballot_paper("Claude", Ballot::Delegated(Voter(String::from("Claude"))))), VoteOutcome::DelegationCycle(_, _)));
+        assert!(voting_machine.get_delegations().get(&Voter(String::from("Bob"))).is_none());
+    }
+
+    #[test]
+    fn proxy_holder_votes_for_the_principal(){
+        let mut voting_machine = setup().with_max_proxies(1);
+        let claude = Voter(String::from("Claude"));
+        let alice = Voter(String::from("Alice"));
+        assert!(matches!(voting_machine.grant_proxy(alice.clone(), claude.clone()), VoteOutcome::ProxyAccepted(_, _)));
+        assert!(matches!(voting_machine.grant_proxy(Voter(String::from("Bob")), claude.clone()), VoteOutcome::ProxyLimitExceeded(_)));
+
+        voting_machine.vote(ballot_paper("Claude", Ballot::Single(Some(candidate("Jean-Marie Bigard")))));
+        let result = voting_machine.vote_by_proxy(claude.clone(), ballot_paper("Alice", Ballot::Single(Some(candidate("Jean-Marie Bigard")))));
+        assert!(matches!(result, VoteOutcome::AcceptedVote(_, _)));
+        assert_eq!(voting_machine.get_scoreboard().scores.get(&Candidate(String::from("Jean-Marie Bigard"))).unwrap().0, 2);
+        assert!(voting_machine.get_voters().0.contains(&alice));
+        assert_eq!(voting_machine.get_proxy_votes().get(&alice), Some(&claude));
+        assert!(matches!(voting_machine.grant_proxy(Voter(String::from("Bob")), claude), VoteOutcome::ProxyLimitExceeded(_)));
+    }
+
+    #[test]
+    fn proxy_vote_requires_a_granted_proxy(){
+        let mut voting_machine = setup();
+        voting_machine.grant_proxy(Voter(String::from("Alice")), Voter(String::from("Claude")));
+
+        let result = voting_machine.vote_by_proxy(Voter(String::from("Bob")), ballot_paper("Alice", Ballot::Single(Some(candidate("Jean-Marie Bigard")))));
+        assert!(matches!(result, VoteOutcome::ProxyNotGranted(_, _)));
+
+        voting_machine.vote(ballot_paper("Alice", Ballot::Single(Some(candidate("Jean-Marie Bigard")))));
+        let result = voting_machine.vote_by_proxy(Voter(String::from("Claude")), ballot_paper("Alice", Ballot::Single(Some(candidate("Jean-Marie Bigard")))));
+        assert!(matches!(result, VoteOutcome::ProxyNotGranted(_, _)));
+        assert!(voting_machine.get_proxy_votes().is_empty());
+    }
+
+    #[test]
+    fn votes_are_only_taken_while_open(){
+        let mut voting_machine = setup().with_state(ElectionState::Draft);
+        let result = voting_machine.vote(ballot_paper("Alice", Ballot::Single(Some(candidate("Jean-Marie Bigard")))));
+        assert!(matches!(result, VoteOutcome::ElectionNotOpen(_, ElectionState::Draft)));
+
+        assert!(matches!(voting_machine.transition(ElectionState::Open), TransitionOutcome::Changed(ElectionState::Draft, ElectionState::Open)));
+        assert!(matches!(voting_machine.vote(ballot_paper("Alice", Ballot::Single(Some(candidate("Jean-Marie Bigard"))))), VoteOutcome::AcceptedVote(_, _)));
+
+        voting_machine.transition(ElectionState::Closed);
+        let result = voting_machine.vote(ballot_paper("Bob", Ballot::Single(Some(candidate("Jean-Marie Bigard")))));
+        assert!(matches!(result, VoteOutcome::ElectionNotOpen(_, ElectionState::Closed)));
+        assert_eq!(voting_machine.get_voters().0.len(), 1);
+    }
+
+    #[test]
+    fn transitions_follow_the_lifecycle(){
+        let mut voting_machine = setup().with_state(ElectionState::Draft);
+        assert!(matches!(voting_machine.transition(ElectionState::Closed), TransitionOutcome::Refused(ElectionState::Draft, ElectionState::Closed)));
+        voting_machine.transition(ElectionState::Open);
+        assert!(matches!(voting_machine.transition(ElectionState::Certified), TransitionOutcome::Refused(_, _)));
+        voting_machine.transition(ElectionState::Closed);
+        assert!(matches!(voting_machine.transition(ElectionState::Open), TransitionOutcome::Refused(_, _)));
+        assert!(matches!(voting_machine.transition(ElectionState::Certified), TransitionOutcome::Changed(_, _)));
+        assert_eq!(voting_machine.get_state(), ElectionState::Certified);
+    }
+
+    #[test]
+    fn hidden_results_are_shown_once_closed(){
+        let mut voting_machine = setup().with_hidden_results(true);
+        assert!(!voting_machine.results_visible());
+        voting_machine.transition(ElectionState::Closed);
+        assert!(voting_machine.results_visible());
+        assert!(setup().results_visible());
+    }
+
+    #[test]
+    fn scheduled_window_opens_and_closes_the_election(){
+        let schedule = |now| setup()
+            .with_state(ElectionState::Draft)
+            .with_schedule(Some(Timestamp(100)), Some(Timestamp(200)))
+            .with_clock(Arc::new(crate::clock::FixedClock(Timestamp(now))));
+
+        let mut voting_machine = schedule(50);
+        let result = voting_machine.vote(ballot_paper("Alice", Ballot::Single(Some(candidate("Jean-Marie Bigard")))));
+        assert!(matches!(result, VoteOutcome::NotYetOpen(_, Timestamp(100))));
+
+        let mut voting_machine = schedule(150);
+        assert!(matches!(voting_machine.vote(ballot_paper("Alice", Ballot::Single(Some(candidate("Jean-Marie Bigard"))))), VoteOutcome::AcceptedVote(_, _)));
+        assert_eq!(voting_machine.get_state(), ElectionState::Open);
+
+        let mut voting_machine = voting_machine.with_clock(Arc::new(crate::clock::FixedClock(Timestamp(200))));
+        let result = voting_machine.vote(ballot_paper("Bob", Ballot::Single(Some(candidate("Jean-Marie Bigard")))));
+        assert!(matches!(result, VoteOutcome::AlreadyClosed(_, Timestamp(200))));
+        assert_eq!(voting_machine.get_state(), ElectionState::Closed);
+    }
+
+    #[test]
+    fn open_election_refuses_votes_before_its_opening_time(){
+        let mut voting_machine = setup()
+            .with_state(ElectionState::Open)
+            .with_schedule(Some(Timestamp(100)), None)
+            .with_clock(Arc::new(crate::clock::FixedClock(Timestamp(50))));
+
+        let result = voting_machine.vote(ballot_paper("Alice", Ballot::Single(Some(candidate("Jean-Marie Bigard")))));
+
+        assert!(matches!(result, VoteOutcome::NotYetOpen(_, Timestamp(100))));
+        assert!(voting_machine.get_ballots().0.is_empty());
+    }
+
+    #[test]
+    fn only_registered_voters_may_vote(){
+        let roll = ElectoralRoll([Voter(String::from("Alice")), Voter(String::from("Bob")), Voter(String::from("Claude"))].into());
+        let mut voting_machine = setup().with_roll(Some(roll));
+
+        let result = voting_machine.vote(ballot_paper("Mallory", Ballot::Single(Some(candidate("Jean-Marie Bigard")))));
+        assert!(matches!(result, VoteOutcome::NotEligible(voter) if voter.0 == "Mallory"));
+        assert!(matches!(voting_machine.vote(ballot_paper("Alice", Ballot::Delegated(Voter(String::from("Mallory"))))), VoteOutcome::NotEligible(_)));
+        assert!(matches!(voting_machine.grant_proxy(Voter(String::from("Bob")), Voter(String::from("Mallory"))), VoteOutcome::NotEligible(_)));
+        assert!(matches!(voting_machine.vote(ballot_paper("Alice", Ballot::Single(Some(candidate("Jean-Marie Bigard"))))), VoteOutcome::AcceptedVote(_, _)));
+
+        assert_eq!(voting_machine.turnout(), Some(Turnout { voted: 1, registered: 3 }));
+        assert_eq!(setup().turnout(), None);
+    }
+
+    #[test]
+    fn votes_require_the_voter_code(){
+        let roll = ElectoralRoll([Voter(String::from("Alice")), Voter(String::from("Bob"))].into());
+        let mut voting_machine = setup().with_state(ElectionState::Draft).with_roll(Some(roll));
+        let CodesOutcome::Issued(codes) = voting_machine.issue_codes() else { panic!("codes should be issued") };
+        assert_eq!(codes.len(), 2);
+        voting_machine.transition(ElectionState::Open);
+        assert!(matches!(voting_machine.issue_codes(), CodesOutcome::Locked(ElectionState::Open)));
+        assert!(matches!(setup().with_state(ElectionState::Draft).issue_codes(), CodesOutcome::NoRoll));
+        assert!(voting_machine.get_code_hashes().values().all(|hash| hash.0.len() == 64));
+
+        let alice = Voter(String::from("Alice"));
+        let with_code = |voter: &str, code: Option<VotingCode>| BallotPaper {
+            code,
+            ..ballot_paper(voter, Ballot::Single(Some(candidate("Jean-Marie Bigard"))))
+        };
+        assert!(matches!(voting_machine.vote(with_code("Alice", None)), VoteOutcome::InvalidCode(_)));
+        assert!(matches!(voting_machine.vote(with_code("Alice", codes.get(&Voter(String::from("Bob"))).cloned())), VoteOutcome::InvalidCode(_)));
+        assert!(matches!(voting_machine.vote(with_code("Alice", codes.get(&alice).cloned())), VoteOutcome::AcceptedVote(_, _)));
+        assert!(matches!(voting_machine.vote(with_code("Alice", codes.get(&alice).cloned())), VoteOutcome::CodeAlreadyUsed(_)));
+        assert!(voting_machine.get_used_codes().contains(&alice));
+    }
+
+    #[test]
+    fn secret_ballots_keep_no_link_to_voters(){
+        let mut voting_machine = setup().with_secret_ballot(true).with_voter_weights(Map::from([(Voter(String::from("Alice")), 3)]));
+        let bigard = Candidate(String::from("Jean-Marie Bigard"));
+
+        let result = voting_machine.vote(ballot_paper("Alice", Ballot::Single(Some(candidate("Jean-Marie Bigard")))));
+        assert!(matches!(result, VoteOutcome::SecretVote(voter) if voter.0 == "Alice"));
+        voting_machine.vote(ballot_paper("Bob", Ballot::Single(Some(candidate("Jean-Marie Bigard")))));
+        assert!(matches!(voting_machine.vote(ballot_paper("Claude", Ballot::Delegated(Voter(String::from("Alice"))))), VoteOutcome::DelegationUnavailable(_)));
+
+        assert_eq!(voting_machine.get_scoreboard().scores.get(&bigard).unwrap().0, 4);
+        assert_eq!(voting_machine.get_head_counts().scores.get(&bigard).unwrap().0, 2);
+        assert!(voting_machine.get_cast_ballots().is_empty());
+        assert_eq!(voting_machine.get_voters().0.len(), 2);
+        assert_eq!(voting_machine.get_ballots().0.len(), 2);
+    }
+
+    #[test]
+    fn revised_ballot_replaces_the_previous_one(){
+        let mut voting_machine = setup()
+            .with_revision(true, Vec::new())
+            .with_clock(Arc::new(crate::clock::FixedClock(Timestamp(42))));
+        let bigard = Candidate(String::from("Jean-Marie Bigard"));
+        let grahargul = Candidate(String::from("Grahargul le Destructeur de Mondes"));
+        voting_machine.vote(ballot_paper("Bob", Ballot::Delegated(Voter(String::from("Alice")))));
+        voting_machine.vote(ballot_paper("Alice", Ballot::Single(Some(candidate("Jean-Marie Bigard")))));
+        assert_eq!(voting_machine.get_scoreboard().scores.get(&bigard).unwrap().0, 2);
+
+        let result = voting_machine.vote(ballot_paper("Alice", Ballot::Single(Some(candidate("Grahargul le Destructeur de Mondes")))));
+        assert!(matches!(result, VoteOutcome::AcceptedVote(_, _)));
+        assert_eq!(voting_machine.get_scoreboard().scores.get(&bigard).unwrap().0, 0);
+        assert_eq!(voting_machine.get_scoreboard().scores.get(&grahargul).unwrap().0, 2);
+        assert_eq!(voting_machine.get_head_counts().scores.get(&grahargul).unwrap().0, 1);
+        assert_eq!(voting_machine.get_ballots().0, vec![WeightedBallot { ballot: Ballot::Single(Some(grahargul.clone())), weight: 2 }]);
+
+        let revision = &voting_machine.get_revisions()[0];
+        assert_eq!(revision.voter, Voter(String::from("Alice")));
+        assert_eq!(revision.previous, Ballot::Single(Some(bigard)));
+        assert_eq!(revision.at, Timestamp(42));
+    }
+
+    #[test]
+    fn revision_needs_the_previous_ballot(){
+        let mut voting_machine = setup().with_revision(true, Vec::new()).with_secret_ballot(true);
+        voting_machine.vote(ballot_paper("Alice", Ballot::Single(Some(candidate("Jean-Marie Bigard")))));
+        assert!(matches!(voting_machine.vote(ballot_paper("Alice", Ballot::Single(Some(candidate("Jean-Marie Bigard"))))), VoteOutcome::HasAlreadyVoted(_)));
+
+        let mut voting_machine = setup();
+        voting_machine.vote(ballot_paper("Alice", Ballot::Single(Some(candidate("Jean-Marie Bigard")))));
+        assert!(matches!(voting_machine.vote(ballot_paper("Alice", Ballot::Single(Some(candidate("Jean-Marie Bigard"))))), VoteOutcome::HasAlreadyVoted(_)));
+    }
+
+    #[test]
+    fn candidates_are_added_in_draft_only(){
+        let mut voting_machine = setup().with_state(ElectionState::Draft);
+        let dupont = Candidate(String::from("Dupont"));
+        assert!(matches!(voting_machine.add_candidate(dupont.clone()), CandidateOutcome::Added(_)));
+        assert!(matches!(voting_machine.add_candidate(dupont), CandidateOutcome::AlreadyRunning(_)));
+
+        voting_machine.transition(ElectionState::Open);
+        assert!(matches!(voting_machine.add_candidate(Candidate(String::from("Durand"))), CandidateOutcome::Locked(ElectionState::Open)));
+        assert!(matches!(voting_machine.vote(ballot_paper("Alice", Ballot::Single(Some(candidate("Dupont"))))), VoteOutcome::AcceptedVote(_, _)));
+    }
+
+    #[test]
+    fn withdrawn_candidate_ballots_follow_the_policy(){
+        let ranked = |voter: &str, ranking: &[&str]| ballot_paper(voter, Ballot::Ranked(ranking.iter().map(|name| candidate(name)).collect()));
+        let bigard = Candidate(String::from("Jean-Marie Bigard"));
+        let grahargul = Candidate(String::from("Grahargul le Destructeur de Mondes"));
+        let ballots = || setup().with_rule(Arc::new(InstantRunoff));
+
+        let mut voting_machine = ballots();
+        voting_machine.vote(ranked("Alice", &["Jean-Marie Bigard", "Grahargul le Destructeur de Mondes"]));
+        voting_machine.vote(ranked("Bob", &["Grahargul le Destructeur de Mondes"]));
+        assert!(matches!(voting_machine.withdraw_candidate(bigard.clone(), WithdrawalPolicy::Transfer), CandidateOutcome::Withdrawn(_)));
+        assert!(!voting_machine.get_scoreboard().scores.contains_key(&bigard));
+        assert_eq!(voting_machine.get_scoreboard().scores.get(&grahargul).unwrap().0, 2);
+        assert_eq!(voting_machine.get_ballots().0.len(), 2);
+
+        let mut voting_machine = ballots();
+        voting_machine.vote(ranked("Alice", &["Jean-Marie Bigard", "Grahargul le Destructeur de Mondes"]));
+        voting_machine.withdraw_candidate(bigard.clone(), WithdrawalPolicy::Blank);
+        assert_eq!(voting_machine.get_scoreboard().blank_score.0, 1);
+        assert_eq!(voting_machine.get_scoreboard().scores.get(&grahargul).unwrap().0, 0);
+
+        let mut voting_machine = ballots();
+        voting_machine.vote(ranked("Alice", &["Jean-Marie Bigard", "Grahargul le Destructeur de Mondes"]));
+        voting_machine.withdraw_candidate(bigard.clone(), WithdrawalPolicy::Invalid);
+        assert_eq!(voting_machine.get_scoreboard().invalid_score.0, 1);
+        assert!(voting_machine.get_ballots().0.is_empty());
+        assert!(matches!(voting_machine.withdraw_candidate(bigard.clone(), WithdrawalPolicy::Invalid), CandidateOutcome::UnknownCandidate(_)));
+
+        let mut voting_machine = setup().with_write_ins(true, Map::new());
+        voting_machine.vote(ballot_paper("Alice", Ballot::Single(Some(candidate("Jean-Marie Bigard")))));
+        voting_machine.withdraw_candidate(bigard, WithdrawalPolicy::Invalid);
+        assert_eq!(voting_machine.get_scoreboard().invalid_score.0, 1);
+        assert!(voting_machine.get_scoreboard().write_ins.is_empty());
+        assert_eq!(voting_machine.get_cast_ballots().get(&Voter(String::from("Alice"))), Some(&Ballot::Invalid));
+    }
+
+    #[test]
+    fn withdrawal_recounts_positional_points(){
+        let candidates = ["Grahargul le Destructeur de Mondes", "Jean-Marie Bigard", "Dupont"].map(|name| Candidate(String::from(name)));
+        let mut voting_machine = VotingMachine::new(Scoreboard::new(candidates.to_vec())).with_rule(Arc::new(Positional(PositionalRule::Borda)));
+        voting_machine.vote(ballot_paper("Alice", Ballot::Ranked(vec![candidate("Grahargul le Destructeur de Mondes"), candidate("Jean-Marie Bigard"), candidate("Dupont")])));
+        voting_machine.vote(ballot_paper("Bob", Ballot::Ranked(vec![candidate("Dupont"), candidate("Grahargul le Destructeur de Mondes")])));
+        assert_eq!(voting_machine.get_scoreboard().scores.get(&Candidate(String::from("Grahargul le Destructeur de Mondes"))).unwrap().0, 3);
+
+        voting_machine.withdraw_candidate(Candidate(String::from("Dupont")), WithdrawalPolicy::Transfer);
+
+        let scoreboard = voting_machine.get_scoreboard();
+        assert_eq!(scoreboard.scores.get(&Candidate(String::from("Grahargul le Destructeur de Mondes"))).unwrap().0, 2);
+        assert_eq!(scoreboard.scores.get(&Candidate(String::from("Jean-Marie Bigard"))).unwrap().0, 0);
+    }
+
+    #[test]
+    fn withdrawn_secret_ballots_keep_their_weight(){
+        let bigard = Candidate(String::from("Jean-Marie Bigard"));
+        let weights = Map::from([(Voter(String::from("Alice")), 3), (Voter(String::from("Bob")), 0)]);
+        let mut voting_machine = setup().with_secret_ballot(true).with_voter_weights(weights);
+        voting_machine.vote(ballot_paper("Alice", Ballot::Single(Some(candidate("Jean-Marie Bigard")))));
+        voting_machine.vote(ballot_paper("Bob", Ballot::Single(Some(candidate("Jean-Marie Bigard")))));
+        voting_machine.vote(ballot_paper("Claude", Ballot::Single(Some(candidate("Grahargul le Destructeur de Mondes")))));
+
+        voting_machine.withdraw_candidate(bigard.clone(), WithdrawalPolicy::Blank);
+
+        assert_eq!(voting_machine.get_scoreboard().blank_score.0, 3);
+        assert_eq!(voting_machine.get_head_counts().blank_score.0, 2);
+        assert!(!voting_machine.get_scoreboard().scores.contains_key(&bigard));
+        assert_eq!(voting_machine.get_ballots().0.len(), 1);
+    }
+
+    #[test]
+    fn renamed_candidate_keeps_their_votes(){
+        let mut voting_machine = setup();
+        voting_machine.vote(ballot_paper("Alice", Ballot::Single(Some(candidate("Jean-Marie Bigard")))));
+        let fixed = Candidate(String::from("Jean-Marie Bigard (corrigé)"));
+        let result = voting_machine.rename_candidate(Candidate(String::from("Jean-Marie Bigard")), fixed.clone());
+        assert!(matches!(result, CandidateOutcome::Renamed(_, _)));
+        assert_eq!(voting_machine.get_scoreboard().scores.get(&fixed).unwrap().0, 1);
+        assert_eq!(voting_machine.get_cast_ballots().get(&Voter(String::from("Alice"))), Some(&Ballot::Single(Some(fixed))));
+
+        let result = voting_machine.rename_candidate(Candidate(String::from("Inconnu")), Candidate(String::from("Autre")));
+        assert!(matches!(result, CandidateOutcome::UnknownCandidate(_)));
+    }
+
+    #[test]
+    fn renamed_candidate_is_renamed_in_history_and_merges(){
+        let bigard = Candidate(String::from("Jean-Marie Bigard"));
+        let fixed = Candidate(String::from("Jean-Marie Bigard (corrigé)"));
+        let mut voting_machine = setup().with_rule(Arc::new(TwoRound)).with_revision(true, Vec::new()).with_write_ins(true, Map::new());
+        voting_machine.vote(ballot_paper("Alice", Ballot::Single(Some(candidate("Grahargul le Destructeur de Mondes")))));
+        voting_machine.vote(ballot_paper("Alice", Ballot::Single(Some(candidate("Jean-Marie Bigard")))));
+        voting_machine.vote(ballot_paper("Bob", Ballot::Single(Some(candidate("Grahargul le Destructeur de Mondes")))));
+        voting_machine.vote(ballot_paper("Claude", Ballot::Single(Some(candidate("Bigard")))));
+        voting_machine.merge_write_ins(&Candidate(String::from("Bigard")), &bigard);
+        voting_machine.vote(ballot_paper("Denise", Ballot::Single(Some(candidate("Grahargul le Destructeur de Mondes")))));
+        assert!(matches!(voting_machine.close_round(), RoundOutcome::SecondRound(_)));
+
+        voting_machine.rename_candidate(bigard.clone(), fixed.clone());
+
+        let renamed = Ballot::Single(Some(fixed.clone()));
+        assert_eq!(voting_machine.get_revisions()[0].replacement, renamed);
+        let round = &voting_machine.get_archived_rounds()[0];
+        assert_eq!(round.scoreboard.scores.get(&fixed).unwrap().0, 2);
+        assert!(!round.head_counts.scores.contains_key(&bigard));
+        assert_eq!(round.ballots.0.iter().filter(|entry| entry.ballot == renamed).count(), 2);
+        assert_eq!(voting_machine.get_write_in_aliases().get(&Candidate(String::from("Bigard"))), Some(&fixed));
+        assert!(matches!(voting_machine.vote(ballot_paper("Denise", Ballot::Single(Some(candidate("bigard"))))), VoteOutcome::AcceptedVote(_, ref candidate) if *candidate == fixed));
+
+        let grahargul = Candidate(String::from("Grahargul le Destructeur de Mondes"));
+        let result = voting_machine.rename_candidate(grahargul.clone(), fixed);
+        assert!(matches!(result, CandidateOutcome::AlreadyRunning(_)));
+    }
+
+    #[test]
+    fn candidates_cannot_take_a_written_in_name(){
+        let mut voting_machine = setup().with_write_ins(true, Map::new());
+        voting_machine.vote(ballot_paper("Alice", Ballot::Single(Some(candidate("Jean Dupont")))));
+
+        let result = voting_machine.rename_candidate(Candidate(String::from("Jean-Marie Bigard")), Candidate(String::from("jean dupont")));
+
+        assert!(matches!(result, CandidateOutcome::WrittenIn(ref name) if name.0 == "Jean Dupont"));
+        assert!(voting_machine.get_scoreboard().scores.contains_key(&Candidate(String::from("Jean-Marie Bigard"))));
+    }
+
+    #[test]
+    fn write_ins_are_grouped_merged_and_promoted(){
+        let mut voting_machine = setup().with_write_ins(true, Map::new());
+        let dupont = Candidate(String::from("Jean Dupont"));
+        let result = voting_machine.vote(ballot_paper("Alice", Ballot::Single(Some(candidate("jean  dupont")))));
+        assert!(matches!(result, VoteOutcome::WriteIn(_, ref name) if *name == dupont));
+        voting_machine.vote(ballot_paper("Bob", Ballot::Single(Some(candidate("JEAN DUPONT")))));
+        voting_machine.vote(ballot_paper("Claude", Ballot::Single(Some(candidate("Jean Dupond")))));
+        voting_machine.vote(ballot_paper("Denise", Ballot::Single(Some(candidate("jean-marie bigard")))));
+        let scoreboard = voting_machine.get_scoreboard();
+        assert_eq!(scoreboard.write_ins.get(&dupont).unwrap().0, 2);
+        assert_eq!(scoreboard.scores.get(&Candidate(String::from("Jean-Marie Bigard"))).unwrap().0, 1);
+        assert_eq!(scoreboard.invalid_score.0, 0);
+        assert_eq!(voting_machine.get_ballots().0.len(), 4);
+
+        let result = voting_machine.merge_write_ins(&Candidate(String::from("jean dupond")), &dupont);
+        assert!(matches!(result, CandidateOutcome::Merged(_, _)));
+        voting_machine.vote(ballot_paper("Emile", Ballot::Single(Some(candidate("Jean Dupond")))));
+        assert_eq!(voting_machine.get_scoreboard().write_ins.get(&dupont).unwrap().0, 4);
+
+        assert!(matches!(voting_machine.promote_write_in(&dupont), CandidateOutcome::Promoted(_)));
+        assert!(voting_machine.get_scoreboard().write_ins.is_empty());
+        assert_eq!(voting_machine.get_scoreboard().scores.get(&dupont).unwrap().0, 4);
+        assert_eq!(voting_machine.get_ballots().0.len(), 5);
+        assert!(matches!(voting_machine.vote(ballot_paper("Fanny", Ballot::Single(Some(candidate("jean dupond"))))), VoteOutcome::AcceptedVote(_, _)));
+        assert_eq!(voting_machine.get_scoreboard().scores.get(&dupont).unwrap().0, 5);
+        assert!(matches!(voting_machine.promote_write_in(&Candidate(String::from("Inconnu"))), CandidateOutcome::UnknownCandidate(_)));
+    }
+
+    #[test]
+    fn promoted_secret_write_ins_reach_the_ballot_box(){
+        let weights = Map::from([(Voter(String::from("Alice")), 3)]);
+        let mut voting_machine = setup().with_secret_ballot(true).with_voter_weights(weights).with_write_ins(true, Map::new());
+        let dupont = Candidate(String::from("Jean Dupont"));
+        voting_machine.vote(ballot_paper("Alice", Ballot::Single(Some(candidate("jean dupont")))));
+        voting_machine.vote(ballot_paper("Bob", Ballot::Single(Some(candidate("Jean Dupont")))));
+
+        assert!(matches!(voting_machine.promote_write_in(&dupont), CandidateOutcome::Promoted(_)));
+
+        assert_eq!(voting_machine.get_scoreboard().scores.get(&dupont).unwrap().0, 4);
+        let mut promoted: Vec<usize> = voting_machine.get_ballots().0.iter()
+            .filter(|entry| entry.ballot == Ballot::Single(Some(dupont.clone())))
+            .map(|entry| entry.weight)
+            .collect();
+        promoted.sort();
+        assert_eq!(promoted, vec![1, 3]);
+    }
+
+    #[test]
+    fn write_ins_merged_into_a_candidate_count_for_them(){
+        let mut voting_machine = setup().with_write_ins(true, Map::new());
+        let grahargul = Candidate(String::from("Grahargul le Destructeur de Mondes"));
+        voting_machine.vote(ballot_paper("Alice", Ballot::Single(Some(candidate("Grahargul")))));
+        voting_machine.vote(ballot_paper("Bob", Ballot::Single(Some(candidate("Grahargul le Destructeur de Mondes")))));
+
+        let result = voting_machine.merge_write_ins(&Candidate(String::from("grahargul")), &grahargul);
+
+        assert!(matches!(result, CandidateOutcome::Merged(_, ref into) if *into == grahargul));
+        assert!(voting_machine.get_scoreboard().write_ins.is_empty());
+        assert_eq!(voting_machine.get_scoreboard().scores.get(&grahargul).unwrap().0, 2);
+        assert_eq!(voting_machine.get_cast_ballots().get(&Voter(String::from("Alice"))), Some(&Ballot::Single(Some(grahargul.clone()))));
+        assert!(voting_machine.get_ballots().0.iter().all(|entry| entry.ballot == Ballot::Single(Some(grahargul.clone()))));
+        assert!(matches!(voting_machine.vote(ballot_paper("Claude", Ballot::Single(Some(candidate("GRAHARGUL"))))), VoteOutcome::AcceptedVote(_, _)));
+        assert_eq!(voting_machine.get_scoreboard().scores.get(&grahargul).unwrap().0, 3);
+    }
+
+    #[test]
+    fn write_ins_need_a_single_choice_rule(){
+        let mut voting_machine = setup().with_rule(Arc::new(Approval)).with_write_ins(true, Map::new());
+        let result = voting_machine.vote(ballot_paper("Alice", Ballot::Single(Some(candidate("Jean Dupont")))));
+
+        assert!(matches!(result, VoteOutcome::InvalidVote(_)));
+        assert!(voting_machine.get_scoreboard().write_ins.is_empty());
+    }
+
+    #[test]
+    fn unknown_names_are_invalid_without_write_ins(){
+        let mut voting_machine = setup();
+        assert!(matches!(voting_machine.vote(ballot_paper("Alice", Ballot::Single(Some(candidate("Jean Dupont"))))), VoteOutcome::InvalidVote(_)));
+        assert!(voting_machine.get_scoreboard().write_ins.is_empty());
+        assert_eq!(voting_machine.get_scoreboard().invalid_score.0, 1);
+    }
+}
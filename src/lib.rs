@@ -1,6 +1,12 @@
+pub mod clock;
 pub mod configuration;
 pub mod app_builder;
-mod domain;
+pub mod domain;
+pub mod liquid;
+pub mod names;
 mod storage;
 mod storages;
-mod use_cases;
\ No newline at end of file
+pub mod tallies;
+pub mod tally;
+mod use_cases;
+pub mod voting_codes;
\ No newline at end of file
@@ -0,0 +1,94 @@
+use std::collections::{BTreeMap as Map, BTreeSet as Set};
+
+use crate::domain::*;
+
+/// Direct voter a delegated vote ends up with, following the delegation chain
+/// from `voter`. `None` when the chain loops or stops at someone who has not
+/// voted.
+pub fn representative<'a>(delegations: &'a Map<Voter, Voter>, direct_voters: &Set<&Voter>, voter: &'a Voter) -> Option<&'a Voter> {
+    let mut visited = Set::new();
+    let mut current = voter;
+    while let Some(next) = delegations.get(current) {
+        if !visited.insert(current) {
+            return None;
+        }
+        current = next;
+    }
+    direct_voters.contains(current).then_some(current)
+}
+
+/// Whether delegating from `delegator` to `delegate` would close a loop.
+pub fn creates_cycle(delegations: &Map<Voter, Voter>, delegator: &Voter, delegate: &Voter) -> bool {
+    let mut visited = Set::new();
+    let mut current = delegate;
+    loop {
+        if current == delegator {
+            return true;
+        }
+        if !visited.insert(current) {
+            return false;
+        }
+        match delegations.get(current) {
+            Some(next) => current = next,
+            None => return false,
+        }
+    }
+}
+
+/// Weight carried by each direct voter: their own plus the weight of every
+/// voter whose delegation chain resolves to them.
+pub fn carried_weights(delegations: &Map<Voter, Voter>, direct_voters: &Set<&Voter>, weight: impl Fn(&Voter) -> usize) -> Map<Voter, usize> {
+    let mut carried: Map<Voter, usize> = direct_voters.iter().map(|voter| ((*voter).clone(), weight(voter))).collect();
+    for delegator in delegations.keys() {
+        if let Some(representative) = representative(delegations, direct_voters, delegator) {
+            if let Some(total) = carried.get_mut(representative) {
+                *total += weight(delegator);
+            }
+        }
+    }
+    carried
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn voter(name: &str) -> Voter {
+        Voter(name.to_string())
+    }
+
+    fn delegations(edges: &[(&str, &str)]) -> Map<Voter, Voter> {
+        edges.iter().map(|(from, to)| (voter(from), voter(to))).collect()
+    }
+
+    #[test]
+    fn chains_resolve_to_the_first_direct_voter() {
+        let delegations = delegations(&[("Alice", "Bob"), ("Bob", "Claude"), ("Dave", "Bob")]);
+        let claude = voter("Claude");
+        let direct_voters = Set::from([&claude]);
+
+        assert_eq!(representative(&delegations, &direct_voters, &voter("Alice")), Some(&claude));
+        let carried = carried_weights(&delegations, &direct_voters, |_| 1);
+        assert_eq!(carried[&claude], 4);
+    }
+
+    #[test]
+    fn loops_and_dangling_chains_carry_nothing() {
+        let delegations = delegations(&[("Alice", "Bob"), ("Bob", "Alice"), ("Dave", "Eve")]);
+        let claude = voter("Claude");
+        let direct_voters = Set::from([&claude]);
+
+        assert_eq!(representative(&delegations, &direct_voters, &voter("Alice")), None);
+        assert_eq!(representative(&delegations, &direct_voters, &voter("Dave")), None);
+        assert_eq!(carried_weights(&delegations, &direct_voters, |_| 1)[&claude], 1);
+    }
+
+    #[test]
+    fn detects_cycles_before_they_are_created() {
+        let delegations = delegations(&[("Alice", "Bob"), ("Bob", "Claude")]);
+
+        assert!(creates_cycle(&delegations, &voter("Claude"), &voter("Alice")));
+        assert!(creates_cycle(&delegations, &voter("Claude"), &voter("Claude")));
+        assert!(!creates_cycle(&delegations, &voter("Dave"), &voter("Alice")));
+    }
+}
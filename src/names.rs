@@ -0,0 +1,139 @@
+use unicode_normalization::char::is_combining_mark;
+use unicode_normalization::UnicodeNormalization;
+use crate::domain::Candidate;
+
+/// How a typed name relates to the candidates running.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NameMatch {
+    /// The candidate's name, their number, or their name up to case, accents,
+    /// hyphens and spacing.
+    Found(Candidate),
+    /// A candidate whose name is a few typos away from what was typed.
+    Suggested(Candidate),
+    Unknown,
+}
+
+/// Spelling a written-in name is grouped under: spaces collapsed and each
+/// word, hyphenated parts included, capitalized.
+pub fn normalize_write_in(name: &str) -> Candidate {
+    let words: Vec<String> = name.split_whitespace()
+        .map(|word| word.split('-').map(capitalize).collect::<Vec<_>>().join("-"))
+        .collect();
+    Candidate(words.join(" "))
+}
+
+fn capitalize(part: &str) -> String {
+    let mut chars = part.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars.flat_map(char::to_lowercase)).collect(),
+        None => String::new(),
+    }
+}
+
+/// Finds the candidate meant by `typed`, which may also be their number in
+/// the listed menu, counting from 1.
+pub fn match_candidate(typed: &str, candidates: &[Candidate]) -> NameMatch {
+    let typed = typed.trim();
+    if typed.is_empty() {
+        return NameMatch::Unknown;
+    }
+    if let Some(candidate) = candidates.iter().find(|candidate| candidate.0 == typed) {
+        return NameMatch::Found(candidate.clone());
+    }
+    if let Ok(number) = typed.parse::<usize>() {
+        if let Some(candidate) = number.checked_sub(1).and_then(|index| candidates.get(index)) {
+            return NameMatch::Found(candidate.clone());
+        }
+    }
+
+    let key = match_key(typed);
+    if let Some(candidate) = candidates.iter().find(|candidate| match_key(&candidate.0) == key) {
+        return NameMatch::Found(candidate.clone());
+    }
+    candidates.iter()
+        .map(|candidate| (edit_distance(&key, &match_key(&candidate.0)), candidate))
+        .filter(|(distance, candidate)| *distance <= typo_tolerance(&candidate.0))
+        .min_by_key(|(distance, _)| *distance)
+        .map_or(NameMatch::Unknown, |(_, candidate)| NameMatch::Suggested(candidate.clone()))
+}
+
+/// Form names are compared under: without accents, lowercase, with hyphens
+/// read as spaces and spaces collapsed.
+pub fn match_key(name: &str) -> String {
+    let stripped: String = name.nfd()
+        .filter(|c| !is_combining_mark(*c))
+        .flat_map(char::to_lowercase)
+        .map(|c| if c == '-' { ' ' } else { c })
+        .collect();
+    stripped.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Number of characters to insert, delete or replace to turn one string
+/// into the other.
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, a_char) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, b_char) in b.iter().enumerate() {
+            let replaced = previous[j] + usize::from(a_char != *b_char);
+            current.push(replaced.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+/// Typos a name can take before it stops being suggested: one per four
+/// characters, and at least one.
+fn typo_tolerance(name: &str) -> usize {
+    (name.chars().count() / 4).max(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn write_ins_are_grouped_by_spelling() {
+        assert_eq!(normalize_write_in("  jean-marie   DUPONT "), Candidate("Jean-Marie Dupont".to_string()));
+        assert_eq!(normalize_write_in("émile zola"), Candidate("Émile Zola".to_string()));
+        assert_eq!(normalize_write_in(""), Candidate(String::new()));
+    }
+
+    fn candidates() -> Vec<Candidate> {
+        vec![Candidate("Jean-Marie Bigard".to_string()), Candidate("Grahargul le Destructeur de Mondes".to_string()), Candidate("Émile".to_string())]
+    }
+
+    #[test]
+    fn names_match_up_to_case_accents_and_spacing() {
+        let bigard = Candidate("Jean-Marie Bigard".to_string());
+        assert_eq!(match_candidate("Jean-Marie Bigard", &candidates()), NameMatch::Found(bigard.clone()));
+        assert_eq!(match_candidate("jean-marie bigard", &candidates()), NameMatch::Found(bigard.clone()));
+        assert_eq!(match_candidate("  JEAN MARIE  BIGARD ", &candidates()), NameMatch::Found(bigard));
+        assert_eq!(match_candidate("emile", &candidates()), NameMatch::Found(Candidate("Émile".to_string())));
+    }
+
+    #[test]
+    fn candidates_are_selected_by_number() {
+        assert_eq!(match_candidate("2", &candidates()), NameMatch::Found(Candidate("Grahargul le Destructeur de Mondes".to_string())));
+        assert_eq!(match_candidate("0", &candidates()), NameMatch::Unknown);
+        assert_eq!(match_candidate("4", &candidates()), NameMatch::Unknown);
+    }
+
+    #[test]
+    fn close_names_are_suggested() {
+        assert_eq!(match_candidate("Jan-Marie Bigar", &candidates()), NameMatch::Suggested(Candidate("Jean-Marie Bigard".to_string())));
+        assert_eq!(match_candidate("Grahargul le destructeur de monde", &candidates()), NameMatch::Suggested(Candidate("Grahargul le Destructeur de Mondes".to_string())));
+        assert_eq!(match_candidate("Ouga Bouga", &candidates()), NameMatch::Unknown);
+        assert_eq!(match_candidate("", &candidates()), NameMatch::Unknown);
+    }
+
+    #[test]
+    fn edit_distance_counts_typos() {
+        assert_eq!(edit_distance("bigard", "bigard"), 0);
+        assert_eq!(edit_distance("bigard", "bigar"), 1);
+        assert_eq!(edit_distance("bigard", "bogart"), 2);
+        assert_eq!(edit_distance("", "abc"), 3);
+    }
+}
@@ -1,10 +1,30 @@
+use anyhow::anyhow;
 use async_trait::async_trait;
 
-use crate::domain::VotingMachine;
+use crate::domain::{ElectionId, Elections, VotingMachine};
+use crate::tally::RuleRegistry;
 
 #[async_trait]
-pub trait Storage where Self: Sized {
-    async fn new(machine: VotingMachine) -> anyhow::Result<Self>;
-    async fn get_voting_machine(&self) -> anyhow::Result<VotingMachine>;
-    async fn put_voting_machine(&mut self, machine: VotingMachine) -> anyhow::Result<()>;
-}
\ No newline at end of file
+pub trait Storage where Self: Sized + Send + Sync {
+    async fn new<Machines: Into<Elections> + Send>(elections: Machines) -> anyhow::Result<Self>;
+    async fn get_elections(&self) -> anyhow::Result<Elections>;
+    async fn put_elections(&mut self, elections: Elections) -> anyhow::Result<()>;
+
+    /// Rules defined outside this crate, for the stores that rebuild the
+    /// counting rule of the machines they load.
+    fn with_rules(self, _rules: RuleRegistry) -> Self {
+        self
+    }
+
+    async fn get_voting_machine(&self, election: &ElectionId) -> anyhow::Result<VotingMachine> {
+        self.get_elections().await?.0
+            .remove(election)
+            .ok_or_else(|| anyhow!("Scrutin inconnu : {}", election.0))
+    }
+
+    async fn put_voting_machine(&mut self, election: &ElectionId, machine: VotingMachine) -> anyhow::Result<()> {
+        let mut elections = self.get_elections().await?;
+        elections.0.insert(election.clone(), machine);
+        self.put_elections(elections).await
+    }
+}
@@ -1,28 +1,203 @@
 use std::collections::{BTreeMap as Map, BTreeSet as Set};
 
-use anyhow::Result;
 use async_trait::async_trait;
 use tokio::{fs::File, io::AsyncWriteExt};
+use crate::clock::Timestamp;
 use crate::{domain::*, storage::Storage};
 use serde::{Deserialize, Serialize};
+use crate::tallies::majority_judgment::GradeScale;
+use crate::tallies::score::ScoreRange;
+use crate::tallies::single_transferable_vote::SurplusTransfer;
+use crate::tally::{build_rule, RuleRegistry, TallyOptions};
 
 const FILEPATH: &str = "machine.json";
 
+#[derive(Serialize, Deserialize)]
+struct RoundDAO {
+    scores: Map<String, usize>,
+    eliminated: Vec<String>,
+    transfers: Map<String, usize>,
+    exhausted: usize,
+}
+
 #[derive(Serialize, Deserialize)]
 struct ScoreboardDAO {
     scores: Map<String, usize>,
     blank_score: usize,
     invalid_score: usize,
+    #[serde(default)]
+    rounds: Vec<RoundDAO>,
+    #[serde(default)]
+    write_ins: Map<String, usize>,
+}
+
+#[derive(Serialize, Deserialize)]
+enum BallotDAO {
+    Single(Option<String>),
+    Ranked(Vec<String>),
+    Approval(Set<String>),
+    Scored(Map<String, usize>),
+    Graded(Map<String, String>),
+    Delegated(String),
+    Invalid,
+}
+
+/// Ballot in the box. Files written before ballots carried their weight hold
+/// bare ballots, read back with a weight of 1.
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum WeightedBallotDAO {
+    Weighted { ballot: BallotDAO, weight: usize },
+    Plain(BallotDAO),
+}
+
+#[derive(Serialize, Deserialize)]
+enum SurplusTransferDAO {
+    Gregory,
+    Meek,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+enum ElectionStateDAO {
+    Draft,
+    #[default]
+    Open,
+    Closed,
+    Certified,
+}
+
+#[derive(Serialize, Deserialize)]
+struct TallyOptionsDAO {
+    seats: usize,
+    surplus: SurplusTransferDAO,
+    weights: Vec<usize>,
+    min_score: usize,
+    max_score: usize,
+    grades: Vec<String>,
+    #[serde(default)]
+    budget: usize,
+}
+
+#[derive(Serialize, Deserialize)]
+struct RevisionDAO {
+    voter: String,
+    previous: BallotDAO,
+    replacement: BallotDAO,
+    at: u64,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ArchivedRoundDAO {
+    voters: Set<String>,
+    scoreboard: ScoreboardDAO,
+    #[serde(default)]
+    head_counts: Option<ScoreboardDAO>,
+    ballots: Vec<WeightedBallotDAO>,
 }
 
 #[derive(Serialize, Deserialize)]
 pub struct VotingMachineDAO{
     voters: Set<String>,
     scoreboard: ScoreboardDAO,
+    #[serde(default)]
+    ballots: Vec<WeightedBallotDAO>,
+    #[serde(default = "default_rule")]
+    rule: String,
+    #[serde(default)]
+    options: TallyOptionsDAO,
+    #[serde(default)]
+    archived_rounds: Vec<ArchivedRoundDAO>,
+    #[serde(default)]
+    spent_credits: Map<String, usize>,
+    #[serde(default)]
+    voter_weights: Map<String, usize>,
+    #[serde(default)]
+    head_counts: Option<ScoreboardDAO>,
+    #[serde(default)]
+    delegations: Map<String, String>,
+    #[serde(default)]
+    cast_ballots: Map<String, BallotDAO>,
+    #[serde(default)]
+    proxies: Map<String, String>,
+    #[serde(default = "default_max_proxies")]
+    max_proxies: usize,
+    #[serde(default)]
+    proxy_votes: Map<String, String>,
+    #[serde(default)]
+    state: ElectionStateDAO,
+    #[serde(default)]
+    hide_results: bool,
+    #[serde(default)]
+    opens_at: Option<u64>,
+    #[serde(default)]
+    closes_at: Option<u64>,
+    #[serde(default)]
+    roll: Option<Set<String>>,
+    #[serde(default)]
+    code_hashes: Map<String, String>,
+    #[serde(default)]
+    used_codes: Set<String>,
+    #[serde(default)]
+    secret_ballot: bool,
+    #[serde(default)]
+    allow_revision: bool,
+    #[serde(default)]
+    revisions: Vec<RevisionDAO>,
+    #[serde(default)]
+    accept_write_ins: bool,
+    #[serde(default)]
+    write_in_aliases: Map<String, String>,
+}
+
+fn default_rule() -> String {
+    String::from("plurality")
+}
+
+fn default_max_proxies() -> usize {
+    1
 }
 
 pub struct FileStore{
     filepath: String,
+    rules: RuleRegistry,
+}
+
+fn scores_to_dao(scores: &Map<Candidate, Score>) -> Map<String, usize> {
+    scores.iter().map(|(candidate, score)| (candidate.0.clone(), score.0)).collect()
+}
+
+fn voter_pairs_to_dao(pairs: &Map<Voter, Voter>) -> Map<String, String> {
+    pairs.iter().map(|(voter, other)| (voter.0.clone(), other.0.clone())).collect()
+}
+
+fn voter_pairs_from_dao(pairs: Map<String, String>) -> Map<Voter, Voter> {
+    pairs.into_iter().map(|(voter, other)| (Voter(voter), Voter(other))).collect()
+}
+
+fn scores_from_dao(scores: Map<String, usize>) -> Map<Candidate, Score> {
+    scores.into_iter().map(|(candidate, score)| (Candidate(candidate), Score(score))).collect()
+}
+
+impl From<Round> for RoundDAO {
+    fn from(round: Round) -> Self {
+        RoundDAO {
+            scores: scores_to_dao(&round.scores),
+            eliminated: round.eliminated.into_iter().map(|c| c.0).collect(),
+            transfers: scores_to_dao(&round.transfers),
+            exhausted: round.exhausted.0,
+        }
+    }
+}
+
+impl From<RoundDAO> for Round {
+    fn from(round_dao: RoundDAO) -> Self {
+        Round {
+            scores: scores_from_dao(round_dao.scores),
+            eliminated: round_dao.eliminated.into_iter().map(Candidate).collect(),
+            transfers: scores_from_dao(round_dao.transfers),
+            exhausted: Score(round_dao.exhausted),
+        }
+    }
 }
 
 impl From<Scoreboard> for ScoreboardDAO {
@@ -31,30 +206,183 @@ impl From<Scoreboard> for ScoreboardDAO {
         for (candidate, score) in &scoreboard.scores {
             scores.insert(
                 candidate.0.clone(),
-                score.0 as usize,   
+                score.0,
             );
         };
         ScoreboardDAO {
             scores,
-            blank_score: scoreboard.blank_score.0 as usize,
-            invalid_score: scoreboard.invalid_score.0 as usize,
+            blank_score: scoreboard.blank_score.0,
+            invalid_score: scoreboard.invalid_score.0,
+            rounds: scoreboard.rounds.into_iter().map(RoundDAO::from).collect(),
+            write_ins: scores_to_dao(&scoreboard.write_ins),
         }
     }
 }
 
 impl From<ScoreboardDAO> for Scoreboard {
-    fn from(scoreboardDAO : ScoreboardDAO) -> Self {
+    fn from(scoreboard_dao : ScoreboardDAO) -> Self {
         let mut scores = Map::new();
-        for(candidate, score) in scoreboardDAO.scores {
+        for(candidate, score) in scoreboard_dao.scores {
             scores.insert(
                 Candidate(candidate),
-                Score(score as usize),
+                Score(score),
             );
         };
         Scoreboard {
             scores,
-            blank_score: Score(scoreboardDAO.blank_score as usize),
-            invalid_score: Score(scoreboardDAO.invalid_score as usize),
+            blank_score: Score(scoreboard_dao.blank_score),
+            invalid_score: Score(scoreboard_dao.invalid_score),
+            rounds: scoreboard_dao.rounds.into_iter().map(Round::from).collect(),
+            write_ins: scores_from_dao(scoreboard_dao.write_ins),
+        }
+    }
+}
+
+impl From<Ballot> for BallotDAO {
+    fn from(ballot: Ballot) -> Self {
+        match ballot {
+            Ballot::Single(candidate) => BallotDAO::Single(candidate.map(|c| c.0)),
+            Ballot::Ranked(ranking) => BallotDAO::Ranked(ranking.into_iter().map(|c| c.0).collect()),
+            Ballot::Approval(approved) => BallotDAO::Approval(approved.into_iter().map(|c| c.0).collect()),
+            Ballot::Scored(scores) => BallotDAO::Scored(scores.into_iter().map(|(c, score)| (c.0, score)).collect()),
+            Ballot::Graded(grades) => BallotDAO::Graded(grades.into_iter().map(|(c, grade)| (c.0, grade.0)).collect()),
+            Ballot::Delegated(delegate) => BallotDAO::Delegated(delegate.0),
+            Ballot::Invalid => BallotDAO::Invalid,
+        }
+    }
+}
+
+impl From<BallotDAO> for Ballot {
+    fn from(ballot_dao: BallotDAO) -> Self {
+        match ballot_dao {
+            BallotDAO::Single(candidate) => Ballot::Single(candidate.map(Candidate)),
+            BallotDAO::Ranked(ranking) => Ballot::Ranked(ranking.into_iter().map(Candidate).collect()),
+            BallotDAO::Approval(approved) => Ballot::Approval(approved.into_iter().map(Candidate).collect()),
+            BallotDAO::Scored(scores) => Ballot::Scored(scores.into_iter().map(|(c, score)| (Candidate(c), score)).collect()),
+            BallotDAO::Graded(grades) => Ballot::Graded(grades.into_iter().map(|(c, grade)| (Candidate(c), Grade(grade))).collect()),
+            BallotDAO::Delegated(delegate) => Ballot::Delegated(Voter(delegate)),
+            BallotDAO::Invalid => Ballot::Invalid,
+        }
+    }
+}
+
+impl From<WeightedBallot> for WeightedBallotDAO {
+    fn from(entry: WeightedBallot) -> Self {
+        WeightedBallotDAO::Weighted { ballot: entry.ballot.into(), weight: entry.weight }
+    }
+}
+
+impl From<WeightedBallotDAO> for WeightedBallot {
+    fn from(entry_dao: WeightedBallotDAO) -> Self {
+        match entry_dao {
+            WeightedBallotDAO::Weighted { ballot, weight } => WeightedBallot { ballot: ballot.into(), weight },
+            WeightedBallotDAO::Plain(ballot) => WeightedBallot { ballot: ballot.into(), weight: 1 },
+        }
+    }
+}
+
+impl From<TallyOptions> for TallyOptionsDAO {
+    fn from(options: TallyOptions) -> Self {
+        TallyOptionsDAO {
+            seats: options.seats,
+            surplus: match options.surplus {
+                SurplusTransfer::Gregory => SurplusTransferDAO::Gregory,
+                SurplusTransfer::Meek => SurplusTransferDAO::Meek,
+            },
+            weights: options.weights,
+            min_score: options.score_range.min,
+            max_score: options.score_range.max,
+            grades: options.grades.0.into_iter().map(|g| g.0).collect(),
+            budget: options.budget,
+        }
+    }
+}
+
+impl From<TallyOptionsDAO> for TallyOptions {
+    fn from(options_dao: TallyOptionsDAO) -> Self {
+        TallyOptions {
+            seats: options_dao.seats,
+            surplus: match options_dao.surplus {
+                SurplusTransferDAO::Gregory => SurplusTransfer::Gregory,
+                SurplusTransferDAO::Meek => SurplusTransfer::Meek,
+            },
+            weights: options_dao.weights,
+            score_range: ScoreRange { min: options_dao.min_score, max: options_dao.max_score },
+            grades: GradeScale(options_dao.grades.into_iter().map(Grade).collect()),
+            budget: options_dao.budget,
+        }
+    }
+}
+
+impl Default for TallyOptionsDAO {
+    fn default() -> Self {
+        TallyOptions::default().into()
+    }
+}
+
+impl From<ArchivedRound> for ArchivedRoundDAO {
+    fn from(round: ArchivedRound) -> Self {
+        ArchivedRoundDAO {
+            voters: round.voters.0.into_iter().map(|v| v.0).collect(),
+            scoreboard: round.scoreboard.into(),
+            head_counts: Some(round.head_counts.into()),
+            ballots: round.ballots.0.into_iter().map(WeightedBallotDAO::from).collect(),
+        }
+    }
+}
+
+impl From<ArchivedRoundDAO> for ArchivedRound {
+    fn from(round_dao: ArchivedRoundDAO) -> Self {
+        let scoreboard = Scoreboard::from(round_dao.scoreboard);
+        ArchivedRound {
+            voters: AttendanceSheet(round_dao.voters.into_iter().map(Voter).collect()),
+            head_counts: round_dao.head_counts.map(Scoreboard::from).unwrap_or_else(|| scoreboard.clone()),
+            scoreboard,
+            ballots: BallotBox(round_dao.ballots.into_iter().map(WeightedBallot::from).collect()),
+        }
+    }
+}
+
+impl From<Revision> for RevisionDAO {
+    fn from(revision: Revision) -> Self {
+        RevisionDAO {
+            voter: revision.voter.0,
+            previous: revision.previous.into(),
+            replacement: revision.replacement.into(),
+            at: revision.at.0,
+        }
+    }
+}
+
+impl From<RevisionDAO> for Revision {
+    fn from(revision_dao: RevisionDAO) -> Self {
+        Revision {
+            voter: Voter(revision_dao.voter),
+            previous: revision_dao.previous.into(),
+            replacement: revision_dao.replacement.into(),
+            at: Timestamp(revision_dao.at),
+        }
+    }
+}
+
+impl From<ElectionState> for ElectionStateDAO {
+    fn from(state: ElectionState) -> Self {
+        match state {
+            ElectionState::Draft => ElectionStateDAO::Draft,
+            ElectionState::Open => ElectionStateDAO::Open,
+            ElectionState::Closed => ElectionStateDAO::Closed,
+            ElectionState::Certified => ElectionStateDAO::Certified,
+        }
+    }
+}
+
+impl From<ElectionStateDAO> for ElectionState {
+    fn from(state_dao: ElectionStateDAO) -> Self {
+        match state_dao {
+            ElectionStateDAO::Draft => ElectionState::Draft,
+            ElectionStateDAO::Open => ElectionState::Open,
+            ElectionStateDAO::Closed => ElectionState::Closed,
+            ElectionStateDAO::Certified => ElectionState::Certified,
         }
     }
 }
@@ -72,30 +400,111 @@ impl From<VotingMachine> for VotingMachineDAO {
         VotingMachineDAO {
             voters,
             scoreboard: scoreboardnew,
+            ballots: votingmachine.get_ballots().0.iter().cloned().map(WeightedBallotDAO::from).collect(),
+            rule: votingmachine.get_rule().name().to_string(),
+            options: votingmachine.get_rule().options().into(),
+            archived_rounds: votingmachine.get_archived_rounds().iter().cloned().map(ArchivedRoundDAO::from).collect(),
+            spent_credits: votingmachine.get_spent_credits().iter().map(|(voter, spent)| (voter.0.clone(), *spent)).collect(),
+            voter_weights: votingmachine.get_voter_weights().iter().map(|(voter, weight)| (voter.0.clone(), *weight)).collect(),
+            head_counts: Some(votingmachine.get_head_counts().clone().into()),
+            delegations: voter_pairs_to_dao(votingmachine.get_delegations()),
+            cast_ballots: votingmachine.get_cast_ballots().iter().map(|(voter, ballot)| (voter.0.clone(), ballot.clone().into())).collect(),
+            proxies: voter_pairs_to_dao(votingmachine.get_proxies()),
+            max_proxies: votingmachine.get_max_proxies(),
+            proxy_votes: voter_pairs_to_dao(votingmachine.get_proxy_votes()),
+            state: votingmachine.get_state().into(),
+            hide_results: votingmachine.hides_results(),
+            opens_at: votingmachine.get_opening_time().map(|time| time.0),
+            closes_at: votingmachine.get_closing_time().map(|time| time.0),
+            roll: votingmachine.get_roll().map(|roll| roll.0.iter().map(|voter| voter.0.clone()).collect()),
+            code_hashes: votingmachine.get_code_hashes().iter().map(|(voter, hash)| (voter.0.clone(), hash.0.clone())).collect(),
+            used_codes: votingmachine.get_used_codes().iter().map(|voter| voter.0.clone()).collect(),
+            secret_ballot: votingmachine.is_secret_ballot(),
+            allow_revision: votingmachine.allows_revision(),
+            revisions: votingmachine.get_revisions().iter().cloned().map(RevisionDAO::from).collect(),
+            accept_write_ins: votingmachine.accepts_write_ins(),
+            write_in_aliases: votingmachine.get_write_in_aliases().iter().map(|(from, into)| (from.0.clone(), into.0.clone())).collect(),
         }
     }
 }
 
-impl From<VotingMachineDAO> for VotingMachine {
-    fn from(votingmachineDAO: VotingMachineDAO) -> Self {
+impl VotingMachineDAO {
+    /// Rebuilds the machine, its counting rule looked up among the builtin
+    /// rules and `rules`.
+    fn into_machine(self, rules: &RuleRegistry) -> anyhow::Result<VotingMachine> {
         let mut voters = Set::new();
-        for voter in votingmachineDAO.voters {
+        for voter in self.voters {
             voters.insert(Voter(voter));
         }
-        let mut scoreboardnew = Scoreboard::from(votingmachineDAO.scoreboard);
-        VotingMachine ::recover_from(AttendanceSheet(voters), scoreboardnew)
+        let scoreboardnew = Scoreboard::from(self.scoreboard);
+        let head_counts = self.head_counts.map(Scoreboard::from).unwrap_or_else(|| scoreboardnew.clone());
+        let ballots = BallotBox(self.ballots.into_iter().map(WeightedBallot::from).collect());
+        let rule = build_rule(&self.rule, &self.options.into(), rules)?;
+        Ok(VotingMachine ::recover_from(AttendanceSheet(voters), scoreboardnew)
+            .with_ballots(ballots)
+            .with_rule(rule)
+            .with_archived_rounds(self.archived_rounds.into_iter().map(ArchivedRound::from).collect())
+            .with_spent_credits(self.spent_credits.into_iter().map(|(voter, spent)| (Voter(voter), spent)).collect())
+            .with_voter_weights(self.voter_weights.into_iter().map(|(voter, weight)| (Voter(voter), weight)).collect())
+            .with_head_counts(head_counts)
+            .with_delegations(voter_pairs_from_dao(self.delegations))
+            .with_cast_ballots(self.cast_ballots.into_iter().map(|(voter, ballot)| (Voter(voter), ballot.into())).collect())
+            .with_proxies(voter_pairs_from_dao(self.proxies), voter_pairs_from_dao(self.proxy_votes))
+            .with_max_proxies(self.max_proxies)
+            .with_state(self.state.into())
+            .with_hidden_results(self.hide_results)
+            .with_schedule(self.opens_at.map(Timestamp), self.closes_at.map(Timestamp))
+            .with_roll(self.roll.map(|roll| ElectoralRoll(roll.into_iter().map(Voter).collect())))
+            .with_codes(
+                self.code_hashes.into_iter().map(|(voter, hash)| (Voter(voter), CodeHash(hash))).collect(),
+                self.used_codes.into_iter().map(Voter).collect(),
+            )
+            .with_secret_ballot(self.secret_ballot)
+            .with_revision(self.allow_revision, self.revisions.into_iter().map(Revision::from).collect())
+            .with_write_ins(
+                self.accept_write_ins,
+                self.write_in_aliases.into_iter().map(|(from, into)| (Candidate(from), Candidate(into))).collect(),
+            ))
     }
 }
 
 
+/// Contents of the store file. Files written before elections were introduced
+/// hold a single machine, which is read back as the default election.
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum StoreDAO {
+    Elections { elections: Map<String, VotingMachineDAO> },
+    Machine(Box<VotingMachineDAO>),
+}
+
+impl From<Elections> for StoreDAO {
+    fn from(elections: Elections) -> Self {
+        StoreDAO::Elections {
+            elections: elections.0.into_iter().map(|(id, machine)| (id.0, machine.into())).collect(),
+        }
+    }
+}
+
+impl StoreDAO {
+    fn into_elections(self, rules: &RuleRegistry) -> anyhow::Result<Elections> {
+        match self {
+            StoreDAO::Elections { elections } => Ok(Elections(elections.into_iter()
+                .map(|(id, machine)| Ok((ElectionId(id), machine.into_machine(rules)?)))
+                .collect::<anyhow::Result<_>>()?)),
+            StoreDAO::Machine(machine) => Ok(machine.into_machine(rules)?.into()),
+        }
+    }
+}
+
 impl FileStore {
-    pub async fn create(machine: VotingMachine, filepath: &str) -> anyhow::Result<Self>{
+    pub async fn create<Machines: Into<Elections>>(elections: Machines, filepath: &str) -> anyhow::Result<Self>{
         let _ = match File::open(filepath).await {
                     Ok(file) => file,
                     Err(_) => {
                         let mut file = File::create(filepath).await?;
-                        let machine_dao = VotingMachineDAO::from(machine);
-                        let json = serde_json::to_vec(&machine_dao)?;
+                        let store_dao = StoreDAO::from(elections.into());
+                        let json = serde_json::to_vec(&store_dao)?;
                         file.write_all(&json).await?;
                         file.flush().await?;
                         File::open(filepath).await?
@@ -104,6 +513,7 @@ impl FileStore {
         
                 Ok(Self {
                     filepath: filepath.to_string(),
+                    rules: RuleRegistry::new(),
                 })
         
     }
@@ -111,30 +521,61 @@ impl FileStore {
 
 #[async_trait]
 impl Storage for FileStore {
-    async fn new(machine: VotingMachine) -> anyhow::Result<Self> {
-        FileStore::create(machine, FILEPATH).await
+    async fn new<Machines: Into<Elections> + Send>(elections: Machines) -> anyhow::Result<Self> {
+        FileStore::create(elections, FILEPATH).await
     }
 
-    async fn get_voting_machine(&self) -> anyhow::Result<VotingMachine> {
+    async fn get_elections(&self) -> anyhow::Result<Elections> {
         let file = File::open(&self.filepath).await?;
-        let machine_dao: VotingMachineDAO = serde_json::from_reader(file.into_std().await)?;
-        Ok(machine_dao.into())
+        let store_dao: StoreDAO = serde_json::from_reader(file.into_std().await)?;
+        store_dao.into_elections(&self.rules)
     }
 
-    async fn put_voting_machine(&mut self, machine: VotingMachine) -> anyhow::Result<()> {
-        let machine_dao: VotingMachineDAO = machine.into();
-        let json = serde_json::to_vec(&machine_dao)?;
+    async fn put_elections(&mut self, elections: Elections) -> anyhow::Result<()> {
+        let store_dao = StoreDAO::from(elections);
+        let json = serde_json::to_vec(&store_dao)?;
         let mut file = File::create(&self.filepath).await?;
         file.write_all(&json).await?;
         file.flush().await?;
         Ok(())
     }
+
+    fn with_rules(mut self, rules: RuleRegistry) -> Self {
+        self.rules = rules;
+        self
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::sync::Arc;
+    use anyhow::Result;
     use tokio::fs;
+    use crate::tallies::instant_runoff::InstantRunoff;
+    use crate::tallies::plurality::{Plurality, TwoRound};
+    use crate::tallies::quadratic::Quadratic;
+    use crate::tallies::score::Star;
+    use crate::tally::{BallotKind, TallyRule};
+
+    /// Plurality under another name, standing for a rule defined outside
+    /// the crate.
+    #[derive(Debug)]
+    struct PluralityBis;
+
+    impl TallyRule for PluralityBis {
+        fn name(&self) -> &str {
+            "plurality-bis"
+        }
+
+        fn ballot_kind(&self) -> BallotKind {
+            BallotKind::Single
+        }
+
+        fn count(&self, voter: Voter, ballot: &Ballot, scoreboard: &mut Scoreboard) -> VoteOutcome {
+            Plurality.count(voter, ballot, scoreboard)
+        }
+    }
 
     fn setup_voting_machine() -> VotingMachine {
         let mut scores = Map::new();
@@ -144,6 +585,8 @@ mod tests {
             scores,
             blank_score: Score(2),
             invalid_score: Score(1),
+            rounds: Vec::new(),
+            write_ins: Map::new(),
         };
 
         let voters = AttendanceSheet(
@@ -163,8 +606,8 @@ mod tests {
         let _ = fs::remove_file(filepath).await;
 
         let mut file_store = FileStore::create(voting_machine.clone(), filepath).await?;
-        file_store.put_voting_machine(voting_machine.clone()).await?;
-        let retrieved_machine = file_store.get_voting_machine().await?;
+        file_store.put_voting_machine(&ElectionId::default(), voting_machine.clone()).await?;
+        let retrieved_machine = file_store.get_voting_machine(&ElectionId::default()).await?;
 
         let dao1 = VotingMachineDAO::from(voting_machine.clone());
         let dao2 = VotingMachineDAO::from(retrieved_machine);
@@ -187,12 +630,12 @@ mod tests {
 
         {
             let mut file_store_1 = FileStore::create(voting_machine.clone(), filepath).await?;
-            file_store_1.put_voting_machine(voting_machine.clone()).await?;
+            file_store_1.put_voting_machine(&ElectionId::default(), voting_machine.clone()).await?;
         }
 
         {
             let file_store_2 = FileStore::create(voting_machine.clone(), filepath).await?;
-            let retrieved_machine = file_store_2.get_voting_machine().await?;
+            let retrieved_machine = file_store_2.get_voting_machine(&ElectionId::default()).await?;
 
             let dao1 = VotingMachineDAO::from(voting_machine.clone());
             let dao2 = VotingMachineDAO::from(retrieved_machine);
@@ -206,5 +649,388 @@ mod tests {
         let _ = fs::remove_file(filepath).await;
         Ok(())
     }
-}
 
+    #[tokio::test]
+    async fn test_ranked_ballots_persistence() -> Result<()> {
+        let filepath = "test_ranked.json";
+        let candidates = vec![Candidate("Alice".to_string()), Candidate("Bob".to_string())];
+        let mut voting_machine = VotingMachine::new(Scoreboard::new(candidates))
+            .with_rule(Arc::new(InstantRunoff));
+        voting_machine.vote(BallotPaper {
+            voter: Voter("John".to_string()),
+            ballot: Ballot::Ranked(vec![Candidate("Bob".to_string()), Candidate("Alice".to_string())]),
+            code: None,
+        });
+
+        let _ = fs::remove_file(filepath).await;
+
+        let mut file_store = FileStore::create(voting_machine.clone(), filepath).await?;
+        file_store.put_voting_machine(&ElectionId::default(), voting_machine.clone()).await?;
+        let retrieved_machine = file_store.get_voting_machine(&ElectionId::default()).await?;
+
+        assert!(retrieved_machine.rule_as::<InstantRunoff>().is_some());
+        assert_eq!(retrieved_machine.get_ballots().0.len(), 1);
+        assert_eq!(retrieved_machine.get_scoreboard().rounds.len(), 1);
+        assert_eq!(retrieved_machine.get_scoreboard().runoff_winner(), Some(&Candidate("Bob".to_string())));
+
+        let _ = fs::remove_file(filepath).await;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_registered_rule_persistence() -> Result<()> {
+        let filepath = "test_registered_rule.json";
+        let rules = RuleRegistry::new().with_rule("plurality-bis", |_| Arc::new(PluralityBis));
+        let candidates = vec![Candidate("Alice".to_string()), Candidate("Bob".to_string())];
+        let voting_machine = VotingMachine::new(Scoreboard::new(candidates))
+            .with_rule(build_rule("plurality-bis", &TallyOptions::default(), &rules)?);
+
+        let _ = fs::remove_file(filepath).await;
+
+        let file_store = FileStore::create(voting_machine, filepath).await?;
+        assert!(file_store.get_voting_machine(&ElectionId::default()).await.is_err());
+
+        let file_store = file_store.with_rules(rules);
+        let retrieved_machine = file_store.get_voting_machine(&ElectionId::default()).await?;
+        assert_eq!(retrieved_machine.get_rule().name(), "plurality-bis");
+
+        let _ = fs::remove_file(filepath).await;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_archived_round_persistence() -> Result<()> {
+        let filepath = "test_two_round.json";
+        let candidates = vec![Candidate("Alice".to_string()), Candidate("Bob".to_string()), Candidate("Charlie".to_string())];
+        let mut voting_machine = VotingMachine::new(Scoreboard::new(candidates))
+            .with_rule(Arc::new(TwoRound));
+        for (voter, candidate) in [("John", "Alice"), ("Jane", "Bob"), ("Jim", "Charlie")] {
+            voting_machine.vote(BallotPaper {
+                voter: Voter(voter.to_string()),
+                ballot: Ballot::Single(Some(Candidate(candidate.to_string()))),
+                code: None,
+            });
+        }
+        voting_machine.close_round();
+
+        let _ = fs::remove_file(filepath).await;
+
+        let mut file_store = FileStore::create(voting_machine.clone(), filepath).await?;
+        file_store.put_voting_machine(&ElectionId::default(), voting_machine.clone()).await?;
+        let retrieved_machine = file_store.get_voting_machine(&ElectionId::default()).await?;
+
+        let archived = retrieved_machine.get_archived_rounds();
+        assert_eq!(archived.len(), 1);
+        assert_eq!(archived[0].voters.0.len(), 3);
+        assert_eq!(archived[0].scoreboard.scores.len(), 3);
+        assert_eq!(retrieved_machine.get_scoreboard().scores.len(), 2);
+
+        let _ = fs::remove_file(filepath).await;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_rule_options_persistence() -> Result<()> {
+        let filepath = "test_rule_options.json";
+        let candidates = vec![Candidate("Alice".to_string()), Candidate("Bob".to_string())];
+        let voting_machine = VotingMachine::new(Scoreboard::new(candidates))
+            .with_rule(Arc::new(Star { range: ScoreRange { min: 1, max: 10 } }));
+
+        let _ = fs::remove_file(filepath).await;
+
+        let file_store = FileStore::create(voting_machine, filepath).await?;
+        let retrieved_machine = file_store.get_voting_machine(&ElectionId::default()).await?;
+
+        assert_eq!(retrieved_machine.get_rule().name(), "star");
+        assert_eq!(retrieved_machine.rule_as::<Star>().map(|rule| rule.range), Some(ScoreRange { min: 1, max: 10 }));
+
+        let _ = fs::remove_file(filepath).await;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_spent_credits_persistence() -> Result<()> {
+        let filepath = "test_spent_credits.json";
+        let candidates = vec![Candidate("Alice".to_string()), Candidate("Bob".to_string())];
+        let mut voting_machine = VotingMachine::new(Scoreboard::new(candidates))
+            .with_rule(Arc::new(Quadratic { credits: 10 }));
+        voting_machine.vote(BallotPaper {
+            voter: Voter("John".to_string()),
+            ballot: Ballot::Scored(Map::from([(Candidate("Alice".to_string()), 3)])),
+            code: None,
+        });
+
+        let _ = fs::remove_file(filepath).await;
+
+        let file_store = FileStore::create(voting_machine, filepath).await?;
+        let retrieved_machine = file_store.get_voting_machine(&ElectionId::default()).await?;
+
+        assert_eq!(retrieved_machine.remaining_credits(&Voter("John".to_string())), Some(1));
+
+        let _ = fs::remove_file(filepath).await;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_voter_weights_persistence() -> Result<()> {
+        let filepath = "test_voter_weights.json";
+        let candidates = vec![Candidate("Alice".to_string()), Candidate("Bob".to_string())];
+        let mut voting_machine = VotingMachine::new(Scoreboard::new(candidates))
+            .with_voter_weights(Map::from([(Voter("John".to_string()), 40)]));
+        voting_machine.vote(BallotPaper {
+            voter: Voter("John".to_string()),
+            ballot: Ballot::Single(Some(Candidate("Bob".to_string()))),
+            code: None,
+        });
+
+        let _ = fs::remove_file(filepath).await;
+
+        let file_store = FileStore::create(voting_machine, filepath).await?;
+        let retrieved_machine = file_store.get_voting_machine(&ElectionId::default()).await?;
+
+        assert_eq!(retrieved_machine.voter_weight(&Voter("John".to_string())), 40);
+        assert_eq!(retrieved_machine.get_scoreboard().scores[&Candidate("Bob".to_string())].0, 40);
+        assert_eq!(retrieved_machine.get_head_counts().scores[&Candidate("Bob".to_string())].0, 1);
+
+        let _ = fs::remove_file(filepath).await;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_delegations_persistence() -> Result<()> {
+        let filepath = "test_delegations.json";
+        let candidates = vec![Candidate("Alice".to_string()), Candidate("Bob".to_string())];
+        let mut voting_machine = VotingMachine::new(Scoreboard::new(candidates));
+        voting_machine.vote(BallotPaper {
+            voter: Voter("Jane".to_string()),
+            ballot: Ballot::Delegated(Voter("John".to_string())),
+            code: None,
+        });
+        voting_machine.vote(BallotPaper {
+            voter: Voter("John".to_string()),
+            ballot: Ballot::Single(Some(Candidate("Bob".to_string()))),
+            code: None,
+        });
+
+        let _ = fs::remove_file(filepath).await;
+
+        let file_store = FileStore::create(voting_machine, filepath).await?;
+        let mut retrieved_machine = file_store.get_voting_machine(&ElectionId::default()).await?;
+
+        assert_eq!(retrieved_machine.get_delegations().get(&Voter("Jane".to_string())), Some(&Voter("John".to_string())));
+        retrieved_machine.vote(BallotPaper {
+            voter: Voter("Jane".to_string()),
+            ballot: Ballot::Single(Some(Candidate("Alice".to_string()))),
+            code: None,
+        });
+        assert_eq!(retrieved_machine.get_scoreboard().scores[&Candidate("Bob".to_string())].0, 1);
+        assert_eq!(retrieved_machine.get_scoreboard().scores[&Candidate("Alice".to_string())].0, 1);
+
+        let _ = fs::remove_file(filepath).await;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_elections_persistence() -> Result<()> {
+        let filepath = "test_elections.json";
+        let budget = ElectionId("budget".to_string());
+        let mut budget_machine = VotingMachine::new(Scoreboard::new(vec![Candidate("Pour".to_string()), Candidate("Contre".to_string())]));
+        budget_machine.vote(BallotPaper {
+            voter: Voter("John".to_string()),
+            ballot: Ballot::Single(Some(Candidate("Pour".to_string()))),
+            code: None,
+        });
+
+        let _ = fs::remove_file(filepath).await;
+
+        let mut file_store = FileStore::create(setup_voting_machine(), filepath).await?;
+        file_store.put_voting_machine(&budget, budget_machine).await?;
+        let elections = file_store.get_elections().await?;
+
+        assert_eq!(elections.0.keys().cloned().collect::<Vec<_>>(), vec![budget.clone(), ElectionId::default()]);
+        assert_eq!(elections.0[&budget].get_scoreboard().scores[&Candidate("Pour".to_string())].0, 1);
+        assert!(elections.0[&ElectionId::default()].get_scoreboard().scores.contains_key(&Candidate("Alice".to_string())));
+
+        let _ = fs::remove_file(filepath).await;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_single_machine_file_loads_as_default_election() -> Result<()> {
+        let filepath = "test_single_machine.json";
+        let machine_dao = VotingMachineDAO::from(setup_voting_machine());
+        fs::write(filepath, serde_json::to_vec(&machine_dao)?).await?;
+
+        let file_store = FileStore::create(setup_voting_machine(), filepath).await?;
+        let retrieved_machine = file_store.get_voting_machine(&ElectionId::default()).await?;
+
+        assert!(retrieved_machine.get_voters().0.contains(&Voter("Jane".to_string())));
+
+        let _ = fs::remove_file(filepath).await;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_state_persistence() -> Result<()> {
+        let filepath = "test_state.json";
+        let mut voting_machine = setup_voting_machine()
+            .with_hidden_results(true)
+            .with_schedule(Some(Timestamp(100)), Some(Timestamp(200)));
+        voting_machine.transition(ElectionState::Closed);
+
+        let _ = fs::remove_file(filepath).await;
+
+        let file_store = FileStore::create(voting_machine, filepath).await?;
+        let retrieved_machine = file_store.get_voting_machine(&ElectionId::default()).await?;
+
+        assert_eq!(retrieved_machine.get_state(), ElectionState::Closed);
+        assert!(retrieved_machine.hides_results());
+        assert_eq!(retrieved_machine.get_opening_time(), Some(Timestamp(100)));
+        assert_eq!(retrieved_machine.get_closing_time(), Some(Timestamp(200)));
+
+        let _ = fs::remove_file(filepath).await;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_roll_persistence() -> Result<()> {
+        let filepath = "test_roll.json";
+        let roll = ElectoralRoll([Voter("John".to_string()), Voter("Jane".to_string()), Voter("Jim".to_string())].into());
+        let mut voting_machine = setup_voting_machine().with_state(ElectionState::Draft).with_roll(Some(roll));
+        let CodesOutcome::Issued(codes) = voting_machine.issue_codes() else { panic!("codes should be issued") };
+        voting_machine.transition(ElectionState::Open);
+
+        let _ = fs::remove_file(filepath).await;
+
+        let file_store = FileStore::create(voting_machine, filepath).await?;
+        let mut retrieved_machine = file_store.get_voting_machine(&ElectionId::default()).await?;
+        let contents = fs::read_to_string(filepath).await?;
+        assert!(codes.values().all(|code| !contents.contains(&code.0)));
+
+        assert!(retrieved_machine.is_eligible(&Voter("Jim".to_string())));
+        assert!(!retrieved_machine.is_eligible(&Voter("Joe".to_string())));
+        assert_eq!(retrieved_machine.turnout(), Some(Turnout { voted: 2, registered: 3 }));
+        let result = retrieved_machine.vote(BallotPaper {
+            voter: Voter("Jim".to_string()),
+            ballot: Ballot::Single(Some(Candidate("Alice".to_string()))),
+            code: codes.get(&Voter("Jim".to_string())).cloned(),
+        });
+        assert!(matches!(result, VoteOutcome::AcceptedVote(_, _)));
+
+        let _ = fs::remove_file(filepath).await;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_secret_ballot_persistence() -> Result<()> {
+        let filepath = "test_secret_ballot.json";
+        let candidates = vec![Candidate("Alice".to_string()), Candidate("Bob".to_string())];
+        let mut voting_machine = VotingMachine::new(Scoreboard::new(candidates)).with_secret_ballot(true);
+        for (voter, candidate) in [("John", "Alice"), ("Jane", "Bob"), ("Jim", "Bob")] {
+            voting_machine.vote(BallotPaper {
+                voter: Voter(voter.to_string()),
+                ballot: Ballot::Single(Some(Candidate(candidate.to_string()))),
+                code: None,
+            });
+        }
+
+        let _ = fs::remove_file(filepath).await;
+
+        let file_store = FileStore::create(voting_machine, filepath).await?;
+        let retrieved_machine = file_store.get_voting_machine(&ElectionId::default()).await?;
+        let stored: serde_json::Value = serde_json::from_str(&fs::read_to_string(filepath).await?)?;
+
+        assert!(retrieved_machine.is_secret_ballot());
+        assert!(retrieved_machine.get_cast_ballots().is_empty());
+        assert_eq!(stored["elections"]["principal"]["cast_ballots"], serde_json::json!({}));
+        assert_eq!(retrieved_machine.get_ballots().0.len(), 3);
+        assert_eq!(retrieved_machine.get_scoreboard().scores[&Candidate("Bob".to_string())].0, 2);
+
+        let _ = fs::remove_file(filepath).await;
+        Ok(())
+    }
+
+    #[test]
+    fn test_ballot_weights_persistence() -> Result<()> {
+        let weighted = WeightedBallot { ballot: Ballot::Single(Some(Candidate("Alice".to_string()))), weight: 3 };
+        let json = serde_json::to_string(&WeightedBallotDAO::from(weighted.clone()))?;
+        assert_eq!(WeightedBallot::from(serde_json::from_str::<WeightedBallotDAO>(&json)?), weighted);
+
+        let legacy: WeightedBallotDAO = serde_json::from_str(r#"{"Single":"Alice"}"#)?;
+        assert_eq!(WeightedBallot::from(legacy), WeightedBallot { weight: 1, ..weighted });
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_revisions_persistence() -> Result<()> {
+        let filepath = "test_revisions.json";
+        let candidates = vec![Candidate("Alice".to_string()), Candidate("Bob".to_string())];
+        let mut voting_machine = VotingMachine::new(Scoreboard::new(candidates)).with_revision(true, Vec::new());
+        for candidate in ["Alice", "Bob"] {
+            voting_machine.vote(BallotPaper {
+                voter: Voter("John".to_string()),
+                ballot: Ballot::Single(Some(Candidate(candidate.to_string()))),
+                code: None,
+            });
+        }
+
+        let _ = fs::remove_file(filepath).await;
+
+        let file_store = FileStore::create(voting_machine, filepath).await?;
+        let mut retrieved_machine = file_store.get_voting_machine(&ElectionId::default()).await?;
+
+        assert!(retrieved_machine.allows_revision());
+        assert_eq!(retrieved_machine.get_revisions().len(), 1);
+        assert_eq!(retrieved_machine.get_revisions()[0].replacement, Ballot::Single(Some(Candidate("Bob".to_string()))));
+        retrieved_machine.vote(BallotPaper {
+            voter: Voter("John".to_string()),
+            ballot: Ballot::Single(Some(Candidate("Alice".to_string()))),
+            code: None,
+        });
+        assert_eq!(retrieved_machine.get_scoreboard().scores[&Candidate("Alice".to_string())].0, 1);
+        assert_eq!(retrieved_machine.get_scoreboard().scores[&Candidate("Bob".to_string())].0, 0);
+
+        let _ = fs::remove_file(filepath).await;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_candidate_changes_persistence() -> Result<()> {
+        let filepath = "test_candidates.json";
+        let mut voting_machine = setup_voting_machine();
+        voting_machine.rename_candidate(Candidate("Alice".to_string()), Candidate("Alicia".to_string()));
+        voting_machine.withdraw_candidate(Candidate("Bob".to_string()), WithdrawalPolicy::Blank);
+
+        let _ = fs::remove_file(filepath).await;
+
+        let file_store = FileStore::create(voting_machine, filepath).await?;
+        let retrieved_machine = file_store.get_voting_machine(&ElectionId::default()).await?;
+
+        let candidates: Vec<Candidate> = retrieved_machine.get_scoreboard().scores.keys().cloned().collect();
+        assert_eq!(candidates, vec![Candidate("Alicia".to_string())]);
+
+        let _ = fs::remove_file(filepath).await;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_write_ins_persistence() -> Result<()> {
+        let filepath = "test_write_ins.json";
+        let mut voting_machine = setup_voting_machine().with_write_ins(true, Map::new());
+        voting_machine.vote(BallotPaper { voter: Voter("Paul".to_string()), ballot: Ballot::Single(Some(Candidate("charlie".to_string()))), code: None });
+        voting_machine.vote(BallotPaper { voter: Voter("Zoe".to_string()), ballot: Ballot::Single(Some(Candidate("Charly".to_string()))), code: None });
+        voting_machine.merge_write_ins(&Candidate("Charly".to_string()), &Candidate("Charlie".to_string()));
+
+        let _ = fs::remove_file(filepath).await;
+
+        let file_store = FileStore::create(voting_machine, filepath).await?;
+        let retrieved_machine = file_store.get_voting_machine(&ElectionId::default()).await?;
+
+        assert!(retrieved_machine.accepts_write_ins());
+        assert_eq!(retrieved_machine.get_scoreboard().write_ins[&Candidate("Charlie".to_string())].0, 2);
+        assert_eq!(retrieved_machine.get_write_in_aliases()[&Candidate("Charly".to_string())], Candidate("Charlie".to_string()));
+
+        let _ = fs::remove_file(filepath).await;
+        Ok(())
+    }
+}
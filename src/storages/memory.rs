@@ -3,23 +3,23 @@ use async_trait::async_trait;
 use anyhow::Result;
 
 pub struct Memory {
-    machine: VotingMachine
+    elections: Elections
 }
 
 #[async_trait]
 impl Storage for Memory {
-    async fn new(machine: VotingMachine) -> Result<Self> {
+    async fn new<Machines: Into<Elections> + Send>(elections: Machines) -> Result<Self> {
         Ok(Self { 
-            machine
+            elections: elections.into()
         })
     }
 
-    async fn get_voting_machine(&self) -> Result<VotingMachine> {
-        Ok(self.machine.clone())
+    async fn get_elections(&self) -> Result<Elections> {
+        Ok(self.elections.clone())
     }
 
-    async fn put_voting_machine(&mut self, machine: VotingMachine) -> Result<()> {
-        self.machine = machine;
+    async fn put_elections(&mut self, elections: Elections) -> Result<()> {
+        self.elections = elections;
         Ok(())
     }
 }
@@ -43,13 +43,27 @@ mod tests {
         let initial_machine = setup();
         let mut storage = Memory::new(initial_machine.clone()).await.unwrap();
         
-        let retrieved_machine = storage.get_voting_machine().await.unwrap();
-        storage.put_voting_machine(retrieved_machine.clone()).await.unwrap();
+        let election = ElectionId::default();
+        let retrieved_machine = storage.get_voting_machine(&election).await.unwrap();
+        storage.put_voting_machine(&election, retrieved_machine.clone()).await.unwrap();
         
-        let final_machine = storage.get_voting_machine().await.unwrap();
+        let final_machine = storage.get_voting_machine(&election).await.unwrap();
         assert_eq!(
             format!("{:?}", initial_machine),
             format!("{:?}", final_machine)
         );
     }
-}
\ No newline at end of file
+
+    #[tokio::test]
+    async fn test_elections_are_kept_apart() {
+        let mut storage = Memory::new(setup()).await.unwrap();
+        let budget = ElectionId("budget".to_string());
+        let budget_machine = VotingMachine::new(Scoreboard::new(vec![Candidate("Pour".to_string()), Candidate("Contre".to_string())]));
+        storage.put_voting_machine(&budget, budget_machine).await.unwrap();
+
+        let elections = storage.get_elections().await.unwrap();
+        assert_eq!(elections.0.len(), 2);
+        assert!(storage.get_voting_machine(&budget).await.unwrap().get_scoreboard().scores.contains_key(&Candidate("Pour".to_string())));
+        assert!(storage.get_voting_machine(&ElectionId("inconnu".to_string())).await.is_err());
+    }
+}
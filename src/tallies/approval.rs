@@ -0,0 +1,68 @@
+use crate::domain::*;
+use crate::tally::{count_approval, count_invalid, BallotKind, TallyRule};
+
+/// One vote for each approved candidate; the most approved candidate wins.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Approval;
+
+impl TallyRule for Approval {
+    fn name(&self) -> &str {
+        "approval"
+    }
+
+    fn ballot_kind(&self) -> BallotKind {
+        BallotKind::Approval
+    }
+
+    fn count(&self, voter: Voter, ballot: &Ballot, scoreboard: &mut Scoreboard) -> VoteOutcome {
+        match ballot {
+            Ballot::Approval(approved) => count_approval(voter, approved, scoreboard),
+            _ => count_invalid(voter, scoreboard),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn candidate(name: &str) -> Candidate {
+        Candidate(name.to_string())
+    }
+
+    fn approval(names: &[&str]) -> Ballot {
+        Ballot::Approval(names.iter().map(|name| candidate(name)).collect())
+    }
+
+    #[test]
+    fn counts_a_vote_for_each_approved_candidate() {
+        let mut scoreboard = Scoreboard::new(vec![candidate("Alice"), candidate("Bob")]);
+
+        let outcome = Approval.count(Voter("Claude".to_string()), &approval(&["Alice", "Bob"]), &mut scoreboard);
+
+        assert!(matches!(outcome, VoteOutcome::AcceptedApproval(_, _)));
+        assert_eq!(scoreboard.scores.get(&candidate("Alice")).unwrap().0, 1);
+        assert_eq!(scoreboard.scores.get(&candidate("Bob")).unwrap().0, 1);
+    }
+
+    #[test]
+    fn unknown_candidates_are_dropped_from_the_approval() {
+        let mut scoreboard = Scoreboard::new(vec![candidate("Alice"), candidate("Bob")]);
+
+        let outcome = Approval.count(Voter("Claude".to_string()), &approval(&["Alice", "Ouga Bouga"]), &mut scoreboard);
+
+        assert!(matches!(&outcome, VoteOutcome::PartiallyInvalidVote(_, unknown) if unknown == &vec![candidate("Ouga Bouga")]));
+        assert_eq!(scoreboard.scores.get(&candidate("Alice")).unwrap().0, 1);
+        assert_eq!(scoreboard.invalid_score.0, 0);
+    }
+
+    #[test]
+    fn approving_only_unknown_candidates_is_invalid() {
+        let mut scoreboard = Scoreboard::new(vec![candidate("Alice"), candidate("Bob")]);
+
+        let outcome = Approval.count(Voter("Claude".to_string()), &approval(&["Ouga Bouga"]), &mut scoreboard);
+
+        assert!(matches!(outcome, VoteOutcome::InvalidVote(_)));
+        assert_eq!(scoreboard.invalid_score.0, 1);
+    }
+}
@@ -0,0 +1,157 @@
+use std::collections::{BTreeMap as Map, BTreeSet as Set};
+
+use crate::domain::*;
+
+/// `matrix.0[a][b]` is the weight of the ballots ranking `a` above `b` (or, for
+/// strongest paths, the strength of the strongest path from `a` to `b`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PairwiseMatrix(pub Map<Candidate, Map<Candidate, usize>>);
+
+impl PairwiseMatrix {
+    fn empty(candidates: &[Candidate]) -> Self {
+        let mut matrix = Map::new();
+        for row in candidates {
+            let mut line = Map::new();
+            for column in candidates {
+                if row != column {
+                    line.insert(column.clone(), 0);
+                }
+            }
+            matrix.insert(row.clone(), line);
+        }
+        PairwiseMatrix(matrix)
+    }
+
+    pub fn get(&self, row: &Candidate, column: &Candidate) -> usize {
+        self.0.get(row).and_then(|line| line.get(column)).copied().unwrap_or(0)
+    }
+
+    fn candidates(&self) -> Vec<Candidate> {
+        self.0.keys().cloned().collect()
+    }
+}
+
+/// Builds the pairwise preference matrix. A ranked candidate is preferred to
+/// every candidate ranked after it and to every unranked candidate; unranked
+/// candidates are not compared with each other. Blank ballots are ignored.
+pub fn pairwise_preferences(candidates: &[Candidate], ballots: &BallotBox) -> PairwiseMatrix {
+    let mut matrix = PairwiseMatrix::empty(candidates);
+    for (ranking, weight) in ballots.weighted_preferences() {
+        let mut above: Set<&Candidate> = Set::new();
+        for preferred in &ranking {
+            above.insert(preferred);
+            let Some(line) = matrix.0.get_mut(preferred) else {
+                continue;
+            };
+            for (other, count) in line.iter_mut() {
+                if !above.contains(other) {
+                    *count += weight;
+                }
+            }
+        }
+    }
+    matrix
+}
+
+/// Candidate beating every other candidate in a head-to-head comparison.
+pub fn condorcet_winner(matrix: &PairwiseMatrix) -> Option<Candidate> {
+    let candidates = matrix.candidates();
+    candidates.iter()
+        .find(|candidate| candidates.iter()
+            .filter(|other| other != candidate)
+            .all(|other| matrix.get(candidate, other) > matrix.get(other, candidate)))
+        .cloned()
+}
+
+/// Strongest path strengths of the Schulze method, computed with the
+/// Floyd–Warshall variant over the winning pairwise margins.
+pub fn strongest_paths(matrix: &PairwiseMatrix) -> PairwiseMatrix {
+    let candidates = matrix.candidates();
+    let mut paths = PairwiseMatrix::empty(&candidates);
+    for row in &candidates {
+        for column in candidates.iter().filter(|column| *column != row) {
+            let strength = matrix.get(row, column);
+            if strength > matrix.get(column, row) {
+                paths.0.get_mut(row).unwrap().insert(column.clone(), strength);
+            }
+        }
+    }
+
+    for middle in &candidates {
+        for row in candidates.iter().filter(|row| *row != middle) {
+            for column in candidates.iter().filter(|column| *column != middle && *column != row) {
+                let through_middle = paths.get(row, middle).min(paths.get(middle, column));
+                if through_middle > paths.get(row, column) {
+                    paths.0.get_mut(row).unwrap().insert(column.clone(), through_middle);
+                }
+            }
+        }
+    }
+    paths
+}
+
+/// Schulze ranking from the strongest paths, best first. Candidates that the
+/// method cannot separate share the same position.
+pub fn schulze_ranking(paths: &PairwiseMatrix) -> Vec<Vec<Candidate>> {
+    let candidates = paths.candidates();
+    let mut wins: Map<usize, Vec<Candidate>> = Map::new();
+    for candidate in &candidates {
+        let beaten = candidates.iter()
+            .filter(|other| *other != candidate && paths.get(candidate, other) > paths.get(other, candidate))
+            .count();
+        wins.entry(beaten).or_default().push(candidate.clone());
+    }
+    wins.into_values().rev().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn candidate(name: &str) -> Candidate {
+        Candidate(name.to_string())
+    }
+
+    fn ballots(groups: &[(usize, &[&str])]) -> BallotBox {
+        let mut ballots = Vec::new();
+        for (count, ranking) in groups {
+            for _ in 0..*count {
+                ballots.push(Ballot::Ranked(ranking.iter().map(|name| candidate(name)).collect()));
+            }
+        }
+        BallotBox::from(ballots)
+    }
+
+    #[test]
+    fn condorcet_winner_beats_everyone() {
+        let candidates = vec![candidate("Alice"), candidate("Bob"), candidate("Charlie")];
+        let ballots = ballots(&[(2, &["Alice", "Bob", "Charlie"]), (1, &["Bob", "Alice"]), (1, &["Charlie"])]);
+
+        let matrix = pairwise_preferences(&candidates, &ballots);
+
+        assert_eq!(matrix.get(&candidate("Alice"), &candidate("Charlie")), 3);
+        assert_eq!(matrix.get(&candidate("Charlie"), &candidate("Alice")), 1);
+        assert_eq!(condorcet_winner(&matrix), Some(candidate("Alice")));
+    }
+
+    #[test]
+    fn schulze_resolves_a_cycle() {
+        let candidates = vec![candidate("Alice"), candidate("Bob"), candidate("Charlie")];
+        let ballots = ballots(&[
+            (4, &["Alice", "Bob", "Charlie"]),
+            (3, &["Bob", "Charlie", "Alice"]),
+            (2, &["Charlie", "Alice", "Bob"]),
+        ]);
+
+        let matrix = pairwise_preferences(&candidates, &ballots);
+        assert_eq!(condorcet_winner(&matrix), None);
+
+        let paths = strongest_paths(&matrix);
+        assert_eq!(paths.get(&candidate("Alice"), &candidate("Charlie")), 6);
+        assert_eq!(paths.get(&candidate("Charlie"), &candidate("Alice")), 5);
+        assert_eq!(
+            schulze_ranking(&paths),
+            vec![vec![candidate("Alice")], vec![candidate("Bob")], vec![candidate("Charlie")]]
+        );
+    }
+}
@@ -0,0 +1,94 @@
+use crate::domain::*;
+use crate::tally::{count_invalid, count_scores, BallotKind, TallyOptions, TallyRule};
+
+/// Each voter spreads at most `budget` points over the candidates, several
+/// points possibly going to the same one; the highest total wins.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cumulative {
+    pub budget: usize,
+}
+
+impl TallyRule for Cumulative {
+    fn name(&self) -> &str {
+        "cumulative"
+    }
+
+    fn ballot_kind(&self) -> BallotKind {
+        BallotKind::Points
+    }
+
+    fn count(&self, voter: Voter, ballot: &Ballot, scoreboard: &mut Scoreboard) -> VoteOutcome {
+        let Ballot::Scored(points) = ballot else {
+            return count_invalid(voter, scoreboard);
+        };
+        let spent = self.cost(ballot);
+        if spent > self.budget {
+            return VoteOutcome::OverBudget(voter, spent);
+        }
+        count_scores(voter, points, scoreboard, None)
+    }
+
+    fn budget(&self) -> Option<usize> {
+        Some(self.budget)
+    }
+
+    fn cost(&self, ballot: &Ballot) -> usize {
+        match ballot {
+            Ballot::Scored(points) => points.values()
+                .try_fold(0usize, |spent, points| spent.checked_add(*points))
+                .unwrap_or(usize::MAX),
+            _ => 0,
+        }
+    }
+
+    fn options(&self) -> TallyOptions {
+        TallyOptions { budget: self.budget, ..TallyOptions::default() }
+    }
+
+    fn awards_points(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn points(entries: &[(&str, usize)]) -> Ballot {
+        Ballot::Scored(entries.iter().map(|(name, points)| (Candidate(name.to_string()), *points)).collect())
+    }
+
+    #[test]
+    fn accumulates_points_given_to_the_same_candidate() {
+        let mut scoreboard = Scoreboard::new(vec![Candidate("Alice".to_string()), Candidate("Bob".to_string())]);
+        let rule = Cumulative { budget: 5 };
+
+        rule.count(Voter("Claude".to_string()), &points(&[("Alice", 5)]), &mut scoreboard);
+        rule.count(Voter("Jane".to_string()), &points(&[("Alice", 2), ("Bob", 3)]), &mut scoreboard);
+
+        assert_eq!(scoreboard.scores[&Candidate("Alice".to_string())].0, 7);
+        assert_eq!(scoreboard.scores[&Candidate("Bob".to_string())].0, 3);
+    }
+
+    #[test]
+    fn rejects_ballots_over_budget() {
+        let mut scoreboard = Scoreboard::new(vec![Candidate("Alice".to_string()), Candidate("Bob".to_string())]);
+
+        let outcome = Cumulative { budget: 5 }.count(Voter("Claude".to_string()), &points(&[("Alice", 4), ("Bob", 2)]), &mut scoreboard);
+
+        assert!(matches!(outcome, VoteOutcome::OverBudget(_, 6)));
+        assert!(scoreboard.scores.values().all(|score| score.0 == 0));
+        assert_eq!(scoreboard.invalid_score.0, 0);
+    }
+
+    #[test]
+    fn overflowing_points_are_over_budget() {
+        let mut scoreboard = Scoreboard::new(vec![Candidate("Alice".to_string()), Candidate("Bob".to_string())]);
+        let half = 1 << (usize::BITS - 1);
+
+        let outcome = Cumulative { budget: 5 }.count(Voter("Claude".to_string()), &points(&[("Alice", half), ("Bob", half)]), &mut scoreboard);
+
+        assert!(matches!(outcome, VoteOutcome::OverBudget(_, usize::MAX)));
+        assert!(scoreboard.scores.values().all(|score| score.0 == 0));
+    }
+}
@@ -0,0 +1,189 @@
+use std::collections::{BTreeMap as Map, BTreeSet as Set};
+
+use crate::domain::*;
+use crate::tally::{count_invalid, count_ranking, BallotKind, TallyRule};
+
+/// Runs an instant-runoff count over the ballot box. Each round counts every
+/// ballot for its highest-ranked continuing candidate; when nobody holds a
+/// majority, the candidates tied for the lowest score are eliminated together
+/// and their ballots move to their next continuing preference. The count stops
+/// on a majority, on a single remaining candidate, or when every remaining
+/// candidate is tied. Each ballot counts for its weight.
+pub fn instant_runoff(candidates: Vec<Candidate>, ballots: &BallotBox) -> Vec<Round> {
+    let preferences = ballots.weighted_preferences();
+    let mut continuing: Set<Candidate> = candidates.into_iter().collect();
+    let mut rounds = Vec::new();
+
+    loop {
+        let mut scores: Map<Candidate, Score> = continuing.iter()
+            .map(|candidate| (candidate.clone(), Score(0)))
+            .collect();
+        for (ranking, weight) in &preferences {
+            if let Some(score) = top_choice(ranking, &continuing).and_then(|c| scores.get_mut(c)) {
+                score.0 += weight;
+            }
+        }
+
+        let total: usize = scores.values().map(|score| score.0).sum();
+        let has_majority = scores.values().any(|score| score.0 * 2 > total);
+        let lowest = scores.values().map(|score| score.0).min().unwrap_or(0);
+        let eliminated: Vec<Candidate> = scores.iter()
+            .filter(|(_, score)| score.0 == lowest)
+            .map(|(candidate, _)| candidate.clone())
+            .collect();
+
+        if has_majority || continuing.len() <= 1 || eliminated.len() == continuing.len() {
+            rounds.push(Round {
+                scores,
+                eliminated: Vec::new(),
+                transfers: Map::new(),
+                exhausted: Score(0),
+            });
+            return rounds;
+        }
+
+        let before_elimination = continuing.clone();
+        for candidate in &eliminated {
+            continuing.remove(candidate);
+        }
+
+        let mut transfers: Map<Candidate, Score> = Map::new();
+        let mut exhausted = Score(0);
+        for (ranking, weight) in &preferences {
+            let was_counted_for_eliminated = top_choice(ranking, &before_elimination)
+                .is_some_and(|candidate| eliminated.contains(candidate));
+            if !was_counted_for_eliminated {
+                continue;
+            }
+            match top_choice(ranking, &continuing) {
+                Some(next) => transfers.entry(next.clone()).or_insert(Score(0)).0 += weight,
+                None => exhausted.0 += weight,
+            }
+        }
+
+        rounds.push(Round { scores, eliminated, transfers, exhausted });
+    }
+}
+
+fn top_choice<'a>(ranking: &'a [Candidate], continuing: &Set<Candidate>) -> Option<&'a Candidate> {
+    ranking.iter().find(|candidate| continuing.contains(candidate))
+}
+
+/// Ranked ballots counted by successive eliminations, the rounds being kept
+/// on the scoreboard.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InstantRunoff;
+
+impl TallyRule for InstantRunoff {
+    fn name(&self) -> &str {
+        "instant-runoff"
+    }
+
+    fn ballot_kind(&self) -> BallotKind {
+        BallotKind::Ranked
+    }
+
+    fn count(&self, voter: Voter, ballot: &Ballot, scoreboard: &mut Scoreboard) -> VoteOutcome {
+        match ballot {
+            Ballot::Ranked(ranking) => count_ranking(voter, ranking, scoreboard, |position| usize::from(position == 0)),
+            _ => count_invalid(voter, scoreboard),
+        }
+    }
+
+    fn tally(&self, ballots: &BallotBox, scoreboard: &mut Scoreboard) {
+        let candidates = scoreboard.scores.keys().cloned().collect();
+        scoreboard.rounds = instant_runoff(candidates, ballots);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn candidate(name: &str) -> Candidate {
+        Candidate(name.to_string())
+    }
+
+    fn ranked(names: &[&str]) -> Ballot {
+        Ballot::Ranked(names.iter().map(|name| candidate(name)).collect())
+    }
+
+    #[test]
+    fn eliminates_and_transfers_until_majority() {
+        let candidates = vec![candidate("Alice"), candidate("Bob"), candidate("Charlie")];
+        let ballots = BallotBox::from(vec![
+            ranked(&["Alice", "Bob"]),
+            ranked(&["Alice"]),
+            ranked(&["Bob", "Alice"]),
+            ranked(&["Bob", "Charlie"]),
+            ranked(&["Charlie", "Bob"]),
+        ]);
+
+        let rounds = instant_runoff(candidates, &ballots);
+
+        assert_eq!(rounds.len(), 2);
+        assert_eq!(rounds[0].eliminated, vec![candidate("Charlie")]);
+        assert_eq!(rounds[0].transfers.get(&candidate("Bob")).unwrap().0, 1);
+        assert_eq!(rounds[1].scores.get(&candidate("Bob")).unwrap().0, 3);
+        assert_eq!(rounds[1].scores.get(&candidate("Alice")).unwrap().0, 2);
+    }
+
+    #[test]
+    fn exhausted_ballots_leave_the_count() {
+        let candidates = vec![candidate("Alice"), candidate("Bob"), candidate("Charlie")];
+        let ballots = BallotBox::from(vec![
+            ranked(&["Alice"]),
+            ranked(&["Alice"]),
+            ranked(&["Bob"]),
+            ranked(&["Bob"]),
+            ranked(&["Charlie"]),
+        ]);
+
+        let rounds = instant_runoff(candidates, &ballots);
+
+        assert_eq!(rounds[0].exhausted.0, 1);
+        let last = rounds.last().unwrap();
+        assert!(last.eliminated.is_empty());
+        assert_eq!(last.scores.len(), 2);
+    }
+
+    #[test]
+    fn blank_ballots_are_ignored() {
+        let candidates = vec![candidate("Alice"), candidate("Bob")];
+        let ballots = BallotBox::from(vec![Ballot::Single(None), ranked(&["Bob"])]);
+
+        let rounds = instant_runoff(candidates, &ballots);
+
+        assert_eq!(rounds.len(), 1);
+        assert_eq!(rounds[0].scores.get(&candidate("Bob")).unwrap().0, 1);
+    }
+
+    #[test]
+    fn counts_first_choices_and_refuses_bad_rankings() {
+        let mut scoreboard = Scoreboard::new(vec![candidate("Alice"), candidate("Bob")]);
+
+        let accepted = InstantRunoff.count(Voter("Claude".to_string()), &ranked(&["Bob", "Alice"]), &mut scoreboard);
+        let unknown = InstantRunoff.count(Voter("Jane".to_string()), &ranked(&["Bob", "Ouga Bouga"]), &mut scoreboard);
+        let duplicated = InstantRunoff.count(Voter("John".to_string()), &ranked(&["Bob", "Bob"]), &mut scoreboard);
+        let blank = InstantRunoff.count(Voter("Dave".to_string()), &ranked(&[]), &mut scoreboard);
+
+        assert!(matches!(accepted, VoteOutcome::AcceptedRanking(_, _)));
+        assert!(matches!(unknown, VoteOutcome::InvalidVote(_)));
+        assert!(matches!(duplicated, VoteOutcome::InvalidVote(_)));
+        assert!(matches!(blank, VoteOutcome::BlankVote(_)));
+        assert_eq!(scoreboard.scores.get(&candidate("Bob")).unwrap().0, 1);
+        assert_eq!(scoreboard.scores.get(&candidate("Alice")).unwrap().0, 0);
+        assert_eq!(scoreboard.invalid_score.0, 2);
+        assert_eq!(scoreboard.blank_score.0, 1);
+    }
+
+    #[test]
+    fn tally_finds_the_runoff_winner() {
+        let mut scoreboard = Scoreboard::new(vec![candidate("Alice"), candidate("Bob")]);
+        let ballots = BallotBox::from(vec![ranked(&["Bob", "Alice"])]);
+
+        InstantRunoff.tally(&ballots, &mut scoreboard);
+
+        assert_eq!(scoreboard.runoff_winner(), Some(&candidate("Bob")));
+    }
+}
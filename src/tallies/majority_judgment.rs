@@ -0,0 +1,306 @@
+use std::cmp::Ordering;
+use std::collections::BTreeMap as Map;
+
+use crate::domain::*;
+use crate::tally::{count_blank, count_invalid, BallotKind, TallyOptions, TallyReport, TallyRule};
+
+/// Grades of a majority judgment election, from the best to the worst.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GradeScale(pub Vec<Grade>);
+
+impl GradeScale {
+    pub fn position(&self, grade: &Grade) -> Option<usize> {
+        self.0.iter().position(|known| known == grade)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct JudgedCandidate {
+    pub candidate: Candidate,
+    pub majority_grade: Option<Grade>,
+    /// Number of ballots giving each grade of the scale, best grade first.
+    pub distribution: Vec<usize>,
+}
+
+/// Ranks the candidates by majority grade, the lower median of their grades
+/// (candidates left ungraded on a ballot receive the worst grade). Ties are
+/// broken with the usual majority-value procedure: the median grade is
+/// removed from each tied candidate's grades and the next median compared,
+/// until the candidates differ. Blank ballots judge no one; the others count
+/// as many times as their weight.
+pub fn majority_judgment(candidates: Vec<Candidate>, ballots: &BallotBox, scale: &GradeScale) -> Vec<JudgedCandidate> {
+    let graded: Vec<(&Map<Candidate, Grade>, usize)> = ballots.0.iter()
+        .filter_map(|entry| match &entry.ballot {
+            Ballot::Graded(grades) if !grades.is_empty() => Some((grades, entry.weight)),
+            _ => None,
+        })
+        .collect();
+    let worst = scale.0.len().saturating_sub(1);
+
+    let mut judged: Vec<(JudgedCandidate, MajorityValue)> = candidates.into_iter()
+        .map(|candidate| {
+            let mut distribution = vec![0; scale.0.len()];
+            for (ballot, weight) in &graded {
+                let grade = ballot.get(&candidate).and_then(|grade| scale.position(grade)).unwrap_or(worst);
+                distribution[grade] += weight;
+            }
+            let majority_value = majority_value(&distribution);
+            let majority_grade = majority_value.median.map(|position| scale.0[position].clone());
+            (JudgedCandidate { candidate, majority_grade, distribution }, majority_value)
+        })
+        .collect();
+
+    judged.sort_by(|(_, a), (_, b)| a.cmp(b));
+    judged.into_iter().map(|(candidate, _)| candidate).collect()
+}
+
+/// Successive lower medians of a candidate's grades, best grade being 0.
+/// Once the first median is removed, the next ones are taken in pairs on
+/// either side of it; the pairs are kept as runs `(pair, count)` so that
+/// weights in the millions are never expanded.
+#[derive(Debug, Default, PartialEq, Eq)]
+struct MajorityValue {
+    median: Option<usize>,
+    pairs: Vec<((usize, usize), usize)>,
+    last: Option<usize>,
+}
+
+impl Ord for MajorityValue {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.median.cmp(&other.median)
+            .then_with(|| compare_runs(&self.pairs, &other.pairs))
+            .then_with(|| self.last.cmp(&other.last))
+    }
+}
+
+impl PartialOrd for MajorityValue {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Compares two run-length encoded sequences as if they were expanded.
+fn compare_runs(a: &[((usize, usize), usize)], b: &[((usize, usize), usize)]) -> Ordering {
+    let (mut a, mut b) = (a.iter().copied(), b.iter().copied());
+    let (mut left, mut right) = (a.next(), b.next());
+    loop {
+        match (left, right) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some((x, _)), Some((y, _))) if x != y => return x.cmp(&y),
+            (Some((x, m)), Some((y, n))) => {
+                let step = m.min(n);
+                left = if m > step { Some((x, m - step)) } else { a.next() };
+                right = if n > step { Some((y, n - step)) } else { b.next() };
+            }
+        }
+    }
+}
+
+/// Majority value of the grades counted in `distribution`: removing the
+/// lower median of the sorted grades at each step takes the median
+/// position, then positions alternately after and before it, starting
+/// with the larger side.
+fn majority_value(distribution: &[usize]) -> MajorityValue {
+    let total: usize = distribution.iter().sum();
+    if total == 0 {
+        return MajorityValue::default();
+    }
+    let starts: Vec<usize> = distribution.iter()
+        .scan(0, |start, count| {
+            let first = *start;
+            *start += count;
+            Some(first)
+        })
+        .collect();
+    let grade_at = |position: usize| starts.iter().rposition(|start| *start <= position).unwrap_or(0);
+
+    let median = total / 2;
+    let pair_count = (total - 1) / 2;
+    let mut pairs = Vec::new();
+    let mut taken = 0;
+    while taken < pair_count {
+        let (before, after) = (median - 1 - taken, median + 1 + taken);
+        let (before_grade, after_grade) = (grade_at(before), grade_at(after));
+        let run = (before + 1 - starts[before_grade])
+            .min(starts[after_grade] + distribution[after_grade] - after)
+            .min(pair_count - taken);
+        let pair = match total % 2 {
+            1 => (after_grade, before_grade),
+            _ => (before_grade, after_grade),
+        };
+        pairs.push((pair, run));
+        taken += run;
+    }
+    let last = (total.is_multiple_of(2) && total > 1).then(|| grade_at(0));
+    MajorityValue { median: Some(grade_at(median)), pairs, last }
+}
+
+/// Graded ballots ranked by `majority_judgment`. Grades leave the scoreboard
+/// untouched.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MajorityJudgment {
+    pub scale: GradeScale,
+}
+
+impl TallyRule for MajorityJudgment {
+    fn name(&self) -> &str {
+        "majority-judgment"
+    }
+
+    fn ballot_kind(&self) -> BallotKind {
+        BallotKind::Graded
+    }
+
+    fn count(&self, voter: Voter, ballot: &Ballot, scoreboard: &mut Scoreboard) -> VoteOutcome {
+        let Ballot::Graded(grades) = ballot else {
+            return count_invalid(voter, scoreboard);
+        };
+        if grades.is_empty() {
+            return count_blank(voter, scoreboard);
+        }
+
+        let valid = grades.iter().all(|(candidate, grade)| {
+            scoreboard.scores.contains_key(candidate) && self.scale.position(grade).is_some()
+        });
+        if !valid {
+            return count_invalid(voter, scoreboard);
+        }
+        VoteOutcome::AcceptedGrades(voter, grades.clone())
+    }
+
+    fn options(&self) -> TallyOptions {
+        TallyOptions { grades: self.scale.clone(), ..TallyOptions::default() }
+    }
+
+    fn report(&self, scoreboard: &Scoreboard, ballots: &BallotBox) -> Option<TallyReport> {
+        let candidates = scoreboard.scores.keys().cloned().collect();
+        Some(TallyReport::MajorityJudgment(majority_judgment(candidates, ballots, &self.scale)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn candidate(name: &str) -> Candidate {
+        Candidate(name.to_string())
+    }
+
+    fn scale() -> GradeScale {
+        GradeScale(["Très bien", "Bien", "Passable", "À rejeter"].iter().map(|g| Grade(g.to_string())).collect())
+    }
+
+    fn ballots(grades: &[&[(&str, &str)]]) -> BallotBox {
+        BallotBox::from(grades.iter()
+            .map(|ballot| Ballot::Graded(ballot.iter().map(|(c, g)| (candidate(c), Grade(g.to_string()))).collect()))
+            .collect::<Vec<Ballot>>())
+    }
+
+    #[test]
+    fn ranks_by_majority_grade() {
+        let ballots = ballots(&[
+            &[("Alice", "Bien"), ("Bob", "Très bien")],
+            &[("Alice", "Bien"), ("Bob", "À rejeter")],
+            &[("Alice", "Passable"), ("Bob", "Passable")],
+        ]);
+
+        let result = majority_judgment(vec![candidate("Alice"), candidate("Bob")], &ballots, &scale());
+
+        assert_eq!(result[0].candidate, candidate("Alice"));
+        assert_eq!(result[0].majority_grade, Some(Grade("Bien".to_string())));
+        assert_eq!(result[1].majority_grade, Some(Grade("Passable".to_string())));
+        assert_eq!(result[1].distribution, vec![1, 0, 1, 1]);
+    }
+
+    #[test]
+    fn breaks_ties_on_the_next_median() {
+        let ballots = ballots(&[
+            &[("Alice", "Très bien"), ("Bob", "Bien")],
+            &[("Alice", "Bien"), ("Bob", "Bien")],
+            &[("Alice", "À rejeter"), ("Bob", "Passable")],
+        ]);
+
+        let result = majority_judgment(vec![candidate("Alice"), candidate("Bob")], &ballots, &scale());
+
+        assert_eq!(result[0].majority_grade, result[1].majority_grade);
+        assert_eq!(result[0].candidate, candidate("Bob"));
+    }
+
+    #[test]
+    fn blank_ballots_judge_no_one() {
+        let ballots = ballots(&[&[("Alice", "Bien")], &[]]);
+
+        let result = majority_judgment(vec![candidate("Alice")], &ballots, &scale());
+
+        assert_eq!(result[0].majority_grade, Some(Grade("Bien".to_string())));
+        assert_eq!(result[0].distribution, vec![0, 1, 0, 0]);
+    }
+
+    /// Majority value computed by removing the lower median one grade at
+    /// a time, as the procedure is described.
+    fn expanded_majority_value(distribution: &[usize]) -> Vec<usize> {
+        let mut grades: Vec<usize> = distribution.iter().enumerate()
+            .flat_map(|(grade, count)| std::iter::repeat_n(grade, *count))
+            .collect();
+        let mut value = Vec::new();
+        while !grades.is_empty() {
+            value.push(grades.remove(grades.len() / 2));
+        }
+        value
+    }
+
+    fn expand(value: &MajorityValue) -> Vec<usize> {
+        value.median.into_iter()
+            .chain(value.pairs.iter().flat_map(|((first, second), count)| [*first, *second].repeat(*count)))
+            .chain(value.last)
+            .collect()
+    }
+
+    #[test]
+    fn majority_value_matches_the_expanded_procedure() {
+        for distribution in [[0, 0, 0], [1, 0, 0], [0, 2, 0], [1, 1, 1], [3, 0, 2], [2, 5, 1], [4, 1, 4], [0, 3, 7]] {
+            assert_eq!(expand(&majority_value(&distribution)), expanded_majority_value(&distribution), "{:?}", distribution);
+        }
+        for (a, b) in [([2, 5, 1], [3, 3, 2]), ([1, 1, 1], [0, 3, 0]), ([4, 1, 4], [3, 3, 3])] {
+            assert_eq!(majority_value(&a).cmp(&majority_value(&b)), expanded_majority_value(&a).cmp(&expanded_majority_value(&b)));
+        }
+    }
+
+    #[test]
+    fn millions_of_shares_are_not_expanded() {
+        let mut ballots = ballots(&[&[("Alice", "Bien"), ("Bob", "Bien")], &[("Alice", "Passable"), ("Bob", "Très bien")]]);
+        ballots.0[0].weight = 3_000_000;
+        ballots.0[1].weight = 2_999_999;
+
+        let result = majority_judgment(vec![candidate("Alice"), candidate("Bob")], &ballots, &scale());
+
+        assert_eq!(result[0].candidate, candidate("Bob"));
+        assert_eq!(result[0].distribution, vec![2_999_999, 3_000_000, 0, 0]);
+    }
+
+    #[test]
+    fn ungraded_candidates_get_the_worst_grade() {
+        let ballots = ballots(&[&[("Alice", "Bien")]]);
+
+        let result = majority_judgment(vec![candidate("Alice"), candidate("Bob")], &ballots, &scale());
+
+        assert_eq!(result[1].majority_grade, Some(Grade("À rejeter".to_string())));
+    }
+
+    #[test]
+    fn counts_known_grades_only() {
+        let mut scoreboard = Scoreboard::new(vec![candidate("Alice"), candidate("Bob")]);
+        let rule = MajorityJudgment { scale: scale() };
+        let mut ballots = ballots(&[&[("Alice", "Bien")], &[("Alice", "Moyen")], &[("Ouga Bouga", "Bien")], &[]]).0.into_iter();
+        let mut count = |voter: &str| rule.count(Voter(voter.to_string()), &ballots.next().unwrap().ballot, &mut scoreboard);
+
+        assert!(matches!(count("Claude"), VoteOutcome::AcceptedGrades(_, _)));
+        assert!(matches!(count("Jane"), VoteOutcome::InvalidVote(_)));
+        assert!(matches!(count("John"), VoteOutcome::InvalidVote(_)));
+        assert!(matches!(count("Dave"), VoteOutcome::BlankVote(_)));
+        assert_eq!(scoreboard.invalid_score.0, 2);
+        assert!(scoreboard.scores.values().all(|score| score.0 == 0));
+    }
+}
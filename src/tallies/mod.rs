@@ -0,0 +1,11 @@
+pub mod approval;
+pub mod condorcet;
+pub mod cumulative;
+pub mod instant_runoff;
+pub mod majority_judgment;
+pub mod plurality;
+pub mod positional;
+pub mod proportional;
+pub mod quadratic;
+pub mod score;
+pub mod single_transferable_vote;
\ No newline at end of file
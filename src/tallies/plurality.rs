@@ -0,0 +1,77 @@
+use crate::domain::*;
+use crate::tally::{count_choice, count_invalid, BallotKind, TallyRule};
+
+/// One vote for the chosen candidate; the most voted candidate wins.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Plurality;
+
+/// Plurality vote where a first round without an absolute majority leads to a
+/// second round between the two leading candidates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TwoRound;
+
+impl TallyRule for Plurality {
+    fn name(&self) -> &str {
+        "plurality"
+    }
+
+    fn ballot_kind(&self) -> BallotKind {
+        BallotKind::Single
+    }
+
+    fn count(&self, voter: Voter, ballot: &Ballot, scoreboard: &mut Scoreboard) -> VoteOutcome {
+        match ballot {
+            Ballot::Single(choice) => count_choice(voter, choice.as_ref(), scoreboard),
+            _ => count_invalid(voter, scoreboard),
+        }
+    }
+}
+
+impl TallyRule for TwoRound {
+    fn name(&self) -> &str {
+        "two-round"
+    }
+
+    fn ballot_kind(&self) -> BallotKind {
+        BallotKind::Single
+    }
+
+    fn count(&self, voter: Voter, ballot: &Ballot, scoreboard: &mut Scoreboard) -> VoteOutcome {
+        Plurality.count(voter, ballot, scoreboard)
+    }
+
+    fn has_second_round(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn setup() -> Scoreboard {
+        Scoreboard::new(vec![Candidate("Alice".to_string()), Candidate("Bob".to_string())])
+    }
+
+    #[test]
+    fn counts_one_vote_for_the_chosen_candidate() {
+        let mut scoreboard = setup();
+        let ballot = Ballot::Single(Some(Candidate("Bob".to_string())));
+
+        let outcome = Plurality.count(Voter("Claude".to_string()), &ballot, &mut scoreboard);
+
+        assert!(matches!(outcome, VoteOutcome::AcceptedVote(_, _)));
+        assert_eq!(scoreboard.scores[&Candidate("Bob".to_string())].0, 1);
+    }
+
+    #[test]
+    fn counts_blank_and_invalid_ballots() {
+        let mut scoreboard = setup();
+
+        Plurality.count(Voter("Claude".to_string()), &Ballot::Single(None), &mut scoreboard);
+        Plurality.count(Voter("Jane".to_string()), &Ballot::Single(Some(Candidate("Ouga Bouga".to_string()))), &mut scoreboard);
+
+        assert_eq!(scoreboard.blank_score.0, 1);
+        assert_eq!(scoreboard.invalid_score.0, 1);
+    }
+}
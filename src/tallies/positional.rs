@@ -0,0 +1,141 @@
+use crate::domain::*;
+use crate::tally::{count_invalid, count_ranking, BallotKind, TallyOptions, TallyRule};
+
+/// Points given to a candidate according to their position on a ranked
+/// ballot. Unranked candidates and positions past the end of a custom weight
+/// vector get no point.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PositionalRule {
+    /// `n - 1` points for the first choice down to 0 for the last one.
+    Borda,
+    /// `1, 1/2, 1/3…` points, scaled by the least common multiple of the
+    /// ranks so that scores stay whole numbers. Past `DOWDALL_MAX_SCALE`,
+    /// which covers 20 candidates, the points are rounded on that scale.
+    Dowdall,
+    Custom(Vec<usize>),
+}
+
+impl PositionalRule {
+    pub fn weights(&self, candidate_count: usize) -> Vec<usize> {
+        match self {
+            PositionalRule::Borda => (0..candidate_count).rev().collect(),
+            PositionalRule::Dowdall => {
+                let scale = (1..=candidate_count)
+                    .try_fold(1, |scale, rank| lcm(scale, rank).filter(|scale| *scale <= DOWDALL_MAX_SCALE))
+                    .unwrap_or(DOWDALL_MAX_SCALE);
+                (1..=candidate_count).map(|rank| (scale + rank / 2) / rank).collect()
+            }
+            PositionalRule::Custom(weights) => weights.clone(),
+        }
+    }
+
+    /// Points earned by each candidate of a ranking, in ranking order.
+    pub fn points<'a>(&self, ranking: &'a [Candidate], candidate_count: usize) -> Vec<(&'a Candidate, usize)> {
+        ranking.iter().zip(self.weights(candidate_count)).collect()
+    }
+}
+
+/// Least common multiple of the ranks 1 to 20, the largest scale Dowdall
+/// points are computed with.
+pub const DOWDALL_MAX_SCALE: usize = 232_792_560;
+
+fn gcd(a: usize, b: usize) -> usize {
+    if b == 0 { a } else { gcd(b, a % b) }
+}
+
+fn lcm(a: usize, b: usize) -> Option<usize> {
+    (a / gcd(a, b)).checked_mul(b)
+}
+
+/// Ranked ballots giving each candidate the points of their position.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Positional(pub PositionalRule);
+
+impl TallyRule for Positional {
+    fn name(&self) -> &str {
+        match self.0 {
+            PositionalRule::Borda => "borda",
+            PositionalRule::Dowdall => "dowdall",
+            PositionalRule::Custom(_) => "positional",
+        }
+    }
+
+    fn ballot_kind(&self) -> BallotKind {
+        BallotKind::Ranked
+    }
+
+    fn count(&self, voter: Voter, ballot: &Ballot, scoreboard: &mut Scoreboard) -> VoteOutcome {
+        let Ballot::Ranked(ranking) = ballot else {
+            return count_invalid(voter, scoreboard);
+        };
+        let weights = self.0.weights(scoreboard.scores.len());
+        count_ranking(voter, ranking, scoreboard, |position| weights.get(position).copied().unwrap_or(0))
+    }
+
+    fn options(&self) -> TallyOptions {
+        match &self.0 {
+            PositionalRule::Custom(weights) => TallyOptions { weights: weights.clone(), ..TallyOptions::default() },
+            _ => TallyOptions::default(),
+        }
+    }
+
+    fn awards_points(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn borda_weights() {
+        assert_eq!(PositionalRule::Borda.weights(4), vec![3, 2, 1, 0]);
+    }
+
+    #[test]
+    fn dowdall_weights_are_scaled_harmonics() {
+        assert_eq!(PositionalRule::Dowdall.weights(4), vec![12, 6, 4, 3]);
+    }
+
+    #[test]
+    fn dowdall_weights_are_rounded_past_twenty_candidates() {
+        assert_eq!(PositionalRule::Dowdall.weights(20)[0], DOWDALL_MAX_SCALE);
+
+        let weights = PositionalRule::Dowdall.weights(100);
+
+        assert_eq!(weights[0], DOWDALL_MAX_SCALE);
+        assert_eq!(weights[2], DOWDALL_MAX_SCALE / 3);
+        assert_eq!(weights[99], 2_327_926);
+        assert!(weights.windows(2).all(|pair| pair[0] > pair[1]));
+    }
+
+    #[test]
+    fn custom_weights_stop_at_the_vector_end() {
+        let rule = PositionalRule::Custom(vec![5, 2]);
+        let ranking = vec![
+            Candidate("Alice".to_string()),
+            Candidate("Bob".to_string()),
+            Candidate("Charlie".to_string()),
+        ];
+
+        let points = rule.points(&ranking, 3);
+
+        assert_eq!(points, vec![(&ranking[0], 5), (&ranking[1], 2)]);
+    }
+
+    #[test]
+    fn ranked_candidates_earn_the_points_of_their_position() {
+        let alice = Candidate("Alice".to_string());
+        let bob = Candidate("Bob".to_string());
+        let mut scoreboard = Scoreboard::new(vec![alice.clone(), bob.clone()]);
+        let rule = Positional(PositionalRule::Custom(vec![3, 1]));
+
+        rule.count(Voter("Claude".to_string()), &Ballot::Ranked(vec![alice.clone(), bob.clone()]), &mut scoreboard);
+        rule.count(Voter("Jane".to_string()), &Ballot::Ranked(vec![bob.clone()]), &mut scoreboard);
+
+        assert_eq!(scoreboard.scores.get(&alice).unwrap().0, 3);
+        assert_eq!(scoreboard.scores.get(&bob).unwrap().0, 4);
+        assert_eq!(scoreboard.ranking()[0].0, &bob);
+    }
+}
@@ -0,0 +1,172 @@
+use std::cmp::Reverse;
+use std::collections::BTreeMap as Map;
+
+use crate::domain::*;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AllocationMethod {
+    DHondt,
+    SainteLague,
+    HareLargestRemainder,
+    DroopLargestRemainder,
+}
+
+#[derive(Debug, Clone)]
+pub struct ProportionalRule {
+    pub method: AllocationMethod,
+    pub seats: usize,
+    /// Minimum share of the expressed votes, in percent, a list needs to take
+    /// part in the allocation.
+    pub threshold: f64,
+}
+
+#[derive(Debug, Clone)]
+pub struct SeatAllocation {
+    pub seats: Map<Candidate, usize>,
+    pub below_threshold: Vec<Candidate>,
+}
+
+/// Distributes the seats between the lists of the scoreboard. Lists below the
+/// threshold get no seat and their votes are not counted in the quotas. Ties
+/// go to the list with the most votes, then to the first one in name order.
+pub fn allocate_seats(scoreboard: &Scoreboard, rule: &ProportionalRule) -> SeatAllocation {
+    let expressed: usize = scoreboard.scores.values().map(|score| score.0).sum();
+    let (eligible, below_threshold): (Vec<_>, Vec<_>) = scoreboard.scores.iter()
+        .map(|(list, score)| (list, score.0))
+        .partition(|(_, votes)| expressed > 0 && *votes as f64 * 100.0 >= rule.threshold * expressed as f64);
+
+    let mut seats: Map<Candidate, usize> = scoreboard.scores.keys().map(|list| (list.clone(), 0)).collect();
+    let won = match rule.method {
+        AllocationMethod::DHondt => highest_averages(&eligible, rule.seats, |won| won + 1),
+        AllocationMethod::SainteLague => highest_averages(&eligible, rule.seats, |won| 2 * won + 1),
+        AllocationMethod::HareLargestRemainder => {
+            let total: usize = eligible.iter().map(|(_, votes)| votes).sum();
+            largest_remainder(&eligible, rule.seats, |votes| (votes * rule.seats, total))
+        }
+        AllocationMethod::DroopLargestRemainder => {
+            let total: usize = eligible.iter().map(|(_, votes)| votes).sum();
+            let quota = total / (rule.seats + 1) + 1;
+            largest_remainder(&eligible, rule.seats, |votes| (votes, quota))
+        }
+    };
+    for (list, count) in won {
+        seats.insert(list.clone(), count);
+    }
+
+    SeatAllocation {
+        seats,
+        below_threshold: below_threshold.into_iter().map(|(list, _)| list.clone()).collect(),
+    }
+}
+
+/// Gives the seats one by one to the list with the highest `votes / divisor`.
+fn highest_averages<'a>(
+    lists: &[(&'a Candidate, usize)],
+    seats: usize,
+    divisor: impl Fn(usize) -> usize,
+) -> Map<&'a Candidate, usize> {
+    let mut won: Map<&Candidate, usize> = lists.iter().map(|(list, _)| (*list, 0)).collect();
+    for _ in 0..seats {
+        let best = lists.iter()
+            .filter(|(_, votes)| *votes > 0)
+            .reduce(|best, current| {
+                // Compares votes_a / divisor_a with votes_b / divisor_b without rounding.
+                let best_average = best.1 * divisor(won[current.0]);
+                let current_average = current.1 * divisor(won[best.0]);
+                if current_average > best_average || (current_average == best_average && current.1 > best.1) {
+                    current
+                } else {
+                    best
+                }
+            });
+        match best {
+            Some((list, _)) => *won.get_mut(list).unwrap() += 1,
+            None => break,
+        }
+    }
+    won
+}
+
+/// Gives each list the whole part of `numerator / quota`, then the remaining
+/// seats to the largest remainders. `share` returns the numerator and the
+/// quota for a list's votes.
+fn largest_remainder<'a>(
+    lists: &[(&'a Candidate, usize)],
+    seats: usize,
+    share: impl Fn(usize) -> (usize, usize),
+) -> Map<&'a Candidate, usize> {
+    let mut won: Map<&Candidate, usize> = Map::new();
+    let mut remainders: Vec<(&Candidate, usize, usize)> = Vec::new();
+    for (list, votes) in lists {
+        let (numerator, quota) = share(*votes);
+        let whole = numerator.checked_div(quota).unwrap_or(0);
+        let remainder = numerator.checked_rem(quota).unwrap_or(0);
+        won.insert(list, whole);
+        remainders.push((list, remainder, *votes));
+    }
+
+    let allocated: usize = won.values().sum();
+    remainders.sort_by_key(|(_, remainder, votes)| Reverse((*remainder, *votes)));
+    for (list, _, _) in remainders.into_iter().take(seats.saturating_sub(allocated)) {
+        *won.get_mut(list).unwrap() += 1;
+    }
+    won
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scoreboard(results: &[(&str, usize)]) -> Scoreboard {
+        let mut scoreboard = Scoreboard::new(results.iter().map(|(list, _)| Candidate(list.to_string())).collect());
+        for (list, votes) in results {
+            scoreboard.scores.get_mut(&Candidate(list.to_string())).unwrap().0 = *votes;
+        }
+        scoreboard
+    }
+
+    fn seats_of(allocation: &SeatAllocation, list: &str) -> usize {
+        allocation.seats[&Candidate(list.to_string())]
+    }
+
+    fn rule(method: AllocationMethod, seats: usize, threshold: f64) -> ProportionalRule {
+        ProportionalRule { method, seats, threshold }
+    }
+
+    fn lists() -> Scoreboard {
+        scoreboard(&[("A", 100_000), ("B", 80_000), ("C", 30_000), ("D", 20_000)])
+    }
+
+    #[test]
+    fn dhondt() {
+        let allocation = allocate_seats(&lists(), &rule(AllocationMethod::DHondt, 8, 0.0));
+        assert_eq!([seats_of(&allocation, "A"), seats_of(&allocation, "B"), seats_of(&allocation, "C"), seats_of(&allocation, "D")], [4, 3, 1, 0]);
+    }
+
+    #[test]
+    fn sainte_lague() {
+        let allocation = allocate_seats(&lists(), &rule(AllocationMethod::SainteLague, 8, 0.0));
+        assert_eq!([seats_of(&allocation, "A"), seats_of(&allocation, "B"), seats_of(&allocation, "C"), seats_of(&allocation, "D")], [3, 3, 1, 1]);
+    }
+
+    #[test]
+    fn hare_largest_remainder() {
+        let allocation = allocate_seats(&lists(), &rule(AllocationMethod::HareLargestRemainder, 8, 0.0));
+        assert_eq!([seats_of(&allocation, "A"), seats_of(&allocation, "B"), seats_of(&allocation, "C"), seats_of(&allocation, "D")], [3, 3, 1, 1]);
+    }
+
+    #[test]
+    fn droop_largest_remainder() {
+        let allocation = allocate_seats(&lists(), &rule(AllocationMethod::DroopLargestRemainder, 8, 0.0));
+        assert_eq!(allocation.seats.values().sum::<usize>(), 8);
+        assert_eq!(seats_of(&allocation, "A"), 4);
+    }
+
+    #[test]
+    fn threshold_excludes_small_lists() {
+        let allocation = allocate_seats(&lists(), &rule(AllocationMethod::DHondt, 8, 10.0));
+        assert_eq!(allocation.below_threshold, vec![Candidate("D".to_string())]);
+        assert_eq!(seats_of(&allocation, "D"), 0);
+        assert_eq!(allocation.seats.values().sum::<usize>(), 8);
+    }
+}
@@ -0,0 +1,124 @@
+use std::collections::BTreeMap as Map;
+
+use crate::domain::*;
+use crate::tally::{count_invalid, count_scores, BallotKind, TallyOptions, TallyReport, TallyRule};
+
+/// Each voter buys votes with `credits`, `k` votes for a candidate costing
+/// `k²` credits; the candidate with the most votes wins.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Quadratic {
+    pub credits: usize,
+}
+
+/// Votes received by each candidate and the credits voters spent on them.
+#[derive(Debug, Clone)]
+pub struct QuadraticResult {
+    pub votes: Map<Candidate, usize>,
+    pub credits: Map<Candidate, usize>,
+}
+
+impl TallyRule for Quadratic {
+    fn name(&self) -> &str {
+        "quadratic"
+    }
+
+    fn ballot_kind(&self) -> BallotKind {
+        BallotKind::Credits
+    }
+
+    fn count(&self, voter: Voter, ballot: &Ballot, scoreboard: &mut Scoreboard) -> VoteOutcome {
+        let Ballot::Scored(votes) = ballot else {
+            return count_invalid(voter, scoreboard);
+        };
+        let spent = self.cost(ballot);
+        if spent > self.credits {
+            return VoteOutcome::OverBudget(voter, spent);
+        }
+        count_scores(voter, votes, scoreboard, None)
+    }
+
+    fn budget(&self) -> Option<usize> {
+        Some(self.credits)
+    }
+
+    fn cost(&self, ballot: &Ballot) -> usize {
+        match ballot {
+            Ballot::Scored(votes) => votes.values()
+                .try_fold(0usize, |spent, votes| votes.checked_mul(*votes).and_then(|cost| spent.checked_add(cost)))
+                .unwrap_or(usize::MAX),
+            _ => 0,
+        }
+    }
+
+    fn options(&self) -> TallyOptions {
+        TallyOptions { budget: self.credits, ..TallyOptions::default() }
+    }
+
+    fn report(&self, scoreboard: &Scoreboard, ballots: &BallotBox) -> Option<TallyReport> {
+        Some(TallyReport::Quadratic(quadratic_result(scoreboard, ballots)))
+    }
+}
+
+pub fn quadratic_result(scoreboard: &Scoreboard, ballots: &BallotBox) -> QuadraticResult {
+    let votes: Map<Candidate, usize> = scoreboard.scores.iter()
+        .map(|(candidate, score)| (candidate.clone(), score.0))
+        .collect();
+    let mut credits: Map<Candidate, usize> = votes.keys().map(|candidate| (candidate.clone(), 0)).collect();
+    for entry in &ballots.0 {
+        if let Ballot::Scored(ballot_votes) = &entry.ballot {
+            for (candidate, count) in ballot_votes {
+                if let Some(spent) = credits.get_mut(candidate) {
+                    *spent += count * count * entry.weight;
+                }
+            }
+        }
+    }
+    QuadraticResult { votes, credits }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn votes(entries: &[(&str, usize)]) -> Ballot {
+        Ballot::Scored(entries.iter().map(|(name, votes)| (Candidate(name.to_string()), *votes)).collect())
+    }
+
+    #[test]
+    fn votes_cost_their_square_in_credits() {
+        let rule = Quadratic { credits: 10 };
+        let mut scoreboard = Scoreboard::new(vec![Candidate("Alice".to_string()), Candidate("Bob".to_string())]);
+
+        assert_eq!(rule.cost(&votes(&[("Alice", 3), ("Bob", 1)])), 10);
+        let outcome = rule.count(Voter("Claude".to_string()), &votes(&[("Alice", 3), ("Bob", 2)]), &mut scoreboard);
+        assert!(matches!(outcome, VoteOutcome::OverBudget(_, 13)));
+    }
+
+    #[test]
+    fn huge_vote_counts_are_over_budget() {
+        let rule = Quadratic { credits: 10 };
+        let mut scoreboard = Scoreboard::new(vec![Candidate("Alice".to_string())]);
+
+        let outcome = rule.count(Voter("Claude".to_string()), &votes(&[("Alice", 1 << 32)]), &mut scoreboard);
+
+        assert!(matches!(outcome, VoteOutcome::OverBudget(_, usize::MAX)));
+        assert_eq!(scoreboard.scores[&Candidate("Alice".to_string())].0, 0);
+    }
+
+    #[test]
+    fn result_reports_votes_and_credits() {
+        let rule = Quadratic { credits: 10 };
+        let mut scoreboard = Scoreboard::new(vec![Candidate("Alice".to_string()), Candidate("Bob".to_string())]);
+        let ballots = BallotBox::from(vec![votes(&[("Alice", 3)]), votes(&[("Alice", 1), ("Bob", 2)])]);
+        for (index, entry) in ballots.0.iter().enumerate() {
+            rule.count(Voter(index.to_string()), &entry.ballot, &mut scoreboard);
+        }
+
+        let result = quadratic_result(&scoreboard, &ballots);
+
+        assert_eq!(result.votes[&Candidate("Alice".to_string())], 4);
+        assert_eq!(result.credits[&Candidate("Alice".to_string())], 10);
+        assert_eq!(result.votes[&Candidate("Bob".to_string())], 2);
+        assert_eq!(result.credits[&Candidate("Bob".to_string())], 4);
+    }
+}
@@ -0,0 +1,249 @@
+use std::cmp::{Ordering, Reverse};
+use std::collections::BTreeMap as Map;
+
+use crate::domain::*;
+use crate::tally::{count_invalid, count_scores, BallotKind, TallyOptions, TallyReport, TallyRule};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ScoreRange {
+    pub min: usize,
+    pub max: usize,
+}
+
+impl ScoreRange {
+    pub fn contains(&self, score: usize) -> bool {
+        (self.min..=self.max).contains(&score)
+    }
+}
+
+/// Outcome of the automatic runoff of a STAR election between the two
+/// highest-scoring candidates.
+#[derive(Debug, Clone)]
+pub struct StarResult {
+    pub finalists: Vec<Candidate>,
+    /// Weight of the ballots scoring each finalist strictly above the other
+    /// one.
+    pub preferences: Map<Candidate, usize>,
+    pub no_preference: usize,
+    pub winner: Option<Candidate>,
+}
+
+/// Score then automatic runoff. The two candidates with the highest total
+/// score (ties broken by the number of maximum scores received) go to a
+/// runoff won by the finalist scored higher on more ballots. A tied runoff
+/// goes to the higher total score, then to the most maximum scores; if the
+/// finalists are still tied there is no winner. Blank ballots take part in
+/// neither stage.
+pub fn star(scoreboard: &Scoreboard, ballots: &BallotBox, range: ScoreRange) -> Option<StarResult> {
+    let score_of = |ballot: &Map<Candidate, usize>, candidate: &Candidate| {
+        ballot.get(candidate).copied().unwrap_or(range.min)
+    };
+    let scored: Vec<(&Map<Candidate, usize>, usize)> = ballots.0.iter()
+        .filter_map(|entry| match &entry.ballot {
+            Ballot::Scored(scores) if !scores.is_empty() => Some((scores, entry.weight)),
+            _ => None,
+        })
+        .collect();
+    let max_scores = |candidate: &Candidate| -> usize {
+        scored.iter()
+            .filter(|(ballot, _)| ballot.get(candidate) == Some(&range.max))
+            .map(|(_, weight)| weight)
+            .sum()
+    };
+
+    let mut standings: Vec<(&Candidate, usize, usize)> = scoreboard.scores.iter()
+        .map(|(candidate, score)| (candidate, score.0, max_scores(candidate)))
+        .collect();
+    standings.sort_by_key(|(_, total, max_count)| Reverse((*total, *max_count)));
+    if standings.len() < 2 {
+        return None;
+    }
+    let (first, second) = (standings[0], standings[1]);
+
+    let mut preferences = Map::from([(first.0.clone(), 0), (second.0.clone(), 0)]);
+    let mut no_preference = 0;
+    for (ballot, weight) in &scored {
+        let (a, b) = (score_of(ballot, first.0), score_of(ballot, second.0));
+        match a.cmp(&b) {
+            Ordering::Greater => *preferences.get_mut(first.0).unwrap() += weight,
+            Ordering::Less => *preferences.get_mut(second.0).unwrap() += weight,
+            Ordering::Equal => no_preference += weight,
+        }
+    }
+
+    let runoff = |finalist: &(&Candidate, usize, usize)| (preferences[finalist.0], finalist.1, finalist.2);
+    let winner = match runoff(&first).cmp(&runoff(&second)) {
+        Ordering::Greater => Some(first.0.clone()),
+        Ordering::Less => Some(second.0.clone()),
+        Ordering::Equal => None,
+    };
+
+    Some(StarResult {
+        finalists: vec![first.0.clone(), second.0.clone()],
+        preferences,
+        no_preference,
+        winner,
+    })
+}
+
+/// Scored ballots; the highest total score wins.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ScoreVoting {
+    pub range: ScoreRange,
+}
+
+/// Scored ballots followed by the automatic runoff computed by `star`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Star {
+    pub range: ScoreRange,
+}
+
+fn count_in_range(voter: Voter, ballot: &Ballot, scoreboard: &mut Scoreboard, range: ScoreRange) -> VoteOutcome {
+    match ballot {
+        Ballot::Scored(scores) => count_scores(voter, scores, scoreboard, Some(range)),
+        _ => count_invalid(voter, scoreboard),
+    }
+}
+
+impl TallyRule for ScoreVoting {
+    fn name(&self) -> &str {
+        "score"
+    }
+
+    fn ballot_kind(&self) -> BallotKind {
+        BallotKind::Scored
+    }
+
+    fn count(&self, voter: Voter, ballot: &Ballot, scoreboard: &mut Scoreboard) -> VoteOutcome {
+        count_in_range(voter, ballot, scoreboard, self.range)
+    }
+
+    fn options(&self) -> TallyOptions {
+        TallyOptions { score_range: self.range, ..TallyOptions::default() }
+    }
+
+    fn awards_points(&self) -> bool {
+        true
+    }
+}
+
+impl TallyRule for Star {
+    fn name(&self) -> &str {
+        "star"
+    }
+
+    fn ballot_kind(&self) -> BallotKind {
+        BallotKind::Scored
+    }
+
+    fn count(&self, voter: Voter, ballot: &Ballot, scoreboard: &mut Scoreboard) -> VoteOutcome {
+        count_in_range(voter, ballot, scoreboard, self.range)
+    }
+
+    fn options(&self) -> TallyOptions {
+        TallyOptions { score_range: self.range, ..TallyOptions::default() }
+    }
+
+    fn awards_points(&self) -> bool {
+        true
+    }
+
+    fn report(&self, scoreboard: &Scoreboard, ballots: &BallotBox) -> Option<TallyReport> {
+        star(scoreboard, ballots, self.range).map(TallyReport::Star)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RANGE: ScoreRange = ScoreRange { min: 0, max: 5 };
+
+    fn candidate(name: &str) -> Candidate {
+        Candidate(name.to_string())
+    }
+
+    fn tally(ballots: &[&[(&str, usize)]]) -> (Scoreboard, BallotBox) {
+        let mut scoreboard = Scoreboard::new(vec![candidate("Alice"), candidate("Bob"), candidate("Charlie")]);
+        let mut ballot_box = BallotBox(Vec::new());
+        for ballot in ballots {
+            let scores: Map<Candidate, usize> = ballot.iter().map(|(name, score)| (candidate(name), *score)).collect();
+            for (candidate, score) in &scores {
+                scoreboard.scores.get_mut(candidate).unwrap().0 += score;
+            }
+            ballot_box.0.push(WeightedBallot { ballot: Ballot::Scored(scores), weight: 1 });
+        }
+        (scoreboard, ballot_box)
+    }
+
+    #[test]
+    fn runoff_can_overturn_the_score_leader() {
+        let (scoreboard, ballots) = tally(&[
+            &[("Alice", 5), ("Bob", 0)],
+            &[("Alice", 5), ("Bob", 0)],
+            &[("Alice", 2), ("Bob", 3)],
+            &[("Alice", 2), ("Bob", 3)],
+            &[("Alice", 2), ("Bob", 3), ("Charlie", 1)],
+        ]);
+
+        let result = star(&scoreboard, &ballots, RANGE).unwrap();
+
+        assert_eq!(result.finalists, vec![candidate("Alice"), candidate("Bob")]);
+        assert_eq!(result.preferences[&candidate("Bob")], 3);
+        assert_eq!(result.winner, Some(candidate("Bob")));
+    }
+
+    #[test]
+    fn tied_runoff_goes_to_the_higher_score() {
+        let (scoreboard, ballots) = tally(&[
+            &[("Alice", 5), ("Bob", 1)],
+            &[("Alice", 2), ("Bob", 3)],
+        ]);
+
+        let result = star(&scoreboard, &ballots, RANGE).unwrap();
+
+        assert_eq!(result.preferences[&candidate("Alice")], 1);
+        assert_eq!(result.preferences[&candidate("Bob")], 1);
+        assert_eq!(result.winner, Some(candidate("Alice")));
+    }
+
+    #[test]
+    fn blank_ballots_express_no_preference() {
+        let (scoreboard, mut ballots) = tally(&[
+            &[("Alice", 5), ("Bob", 1)],
+            &[("Alice", 2), ("Bob", 3)],
+        ]);
+        ballots.0.push(WeightedBallot { ballot: Ballot::Scored(Map::new()), weight: 1 });
+
+        let result = star(&scoreboard, &ballots, RANGE).unwrap();
+
+        assert_eq!(result.no_preference, 0);
+    }
+
+    fn scores(entries: &[(&str, usize)]) -> Ballot {
+        Ballot::Scored(entries.iter().map(|(name, score)| (candidate(name), *score)).collect())
+    }
+
+    #[test]
+    fn scores_add_up() {
+        let mut scoreboard = Scoreboard::new(vec![candidate("Alice"), candidate("Bob")]);
+        let rule = ScoreVoting { range: RANGE };
+
+        rule.count(Voter("Claude".to_string()), &scores(&[("Alice", 4), ("Bob", 1)]), &mut scoreboard);
+        let outcome = rule.count(Voter("Jane".to_string()), &scores(&[("Alice", 3)]), &mut scoreboard);
+
+        assert!(matches!(outcome, VoteOutcome::AcceptedScores(_, _)));
+        assert_eq!(scoreboard.scores.get(&candidate("Alice")).unwrap().0, 7);
+        assert_eq!(scoreboard.scores.get(&candidate("Bob")).unwrap().0, 1);
+    }
+
+    #[test]
+    fn out_of_range_scores_are_invalid() {
+        let mut scoreboard = Scoreboard::new(vec![candidate("Alice"), candidate("Bob")]);
+
+        let outcome = Star { range: RANGE }.count(Voter("Claude".to_string()), &scores(&[("Alice", 6)]), &mut scoreboard);
+
+        assert!(matches!(outcome, VoteOutcome::InvalidVote(_)));
+        assert_eq!(scoreboard.scores.get(&candidate("Alice")).unwrap().0, 0);
+    }
+}
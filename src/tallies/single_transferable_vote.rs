@@ -0,0 +1,364 @@
+use std::collections::{BTreeMap as Map, BTreeSet as Set, VecDeque};
+
+use crate::domain::*;
+use crate::tally::{count_invalid, count_ranking, BallotKind, TallyOptions, TallyReport, TallyRule};
+
+/// Keep values and tallies are considered converged below this difference.
+const MEEK_TOLERANCE: f64 = 1e-9;
+const MEEK_MAX_ITERATIONS: usize = 1000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SurplusTransfer {
+    Gregory,
+    Meek,
+}
+
+/// One stage of a single transferable vote count: the tallies at the start of
+/// the stage, the candidates elected or eliminated during it and where the
+/// transferred votes went.
+#[derive(Debug, Clone)]
+pub struct Stage {
+    pub tallies: Map<Candidate, f64>,
+    pub elected: Vec<Candidate>,
+    pub eliminated: Vec<Candidate>,
+    pub surplus_of: Option<Candidate>,
+    pub transfers: Map<Candidate, f64>,
+    pub exhausted: f64,
+}
+
+#[derive(Debug, Clone)]
+pub struct TransferableVoteResult {
+    pub quota: f64,
+    pub elected: Vec<Candidate>,
+    pub stages: Vec<Stage>,
+}
+
+/// Droop quota: the smallest whole number of votes that only `seats`
+/// candidates can reach.
+pub fn droop_quota(valid_votes: usize, seats: usize) -> usize {
+    valid_votes / (seats + 1) + 1
+}
+
+pub fn single_transferable_vote(
+    candidates: Vec<Candidate>,
+    ballots: &BallotBox,
+    seats: usize,
+    surplus: SurplusTransfer,
+) -> TransferableVoteResult {
+    let preferences = ballots.weighted_preferences();
+    match surplus {
+        SurplusTransfer::Gregory => gregory(candidates, preferences, seats),
+        SurplusTransfer::Meek => meek(candidates, preferences, seats),
+    }
+}
+
+struct Parcel {
+    preferences: Vec<Candidate>,
+    weight: f64,
+    holder: Option<Candidate>,
+}
+
+impl Parcel {
+    fn next_holder(&self, hopeful: &Set<Candidate>) -> Option<Candidate> {
+        self.preferences.iter().find(|candidate| hopeful.contains(*candidate)).cloned()
+    }
+}
+
+/// Weighted inclusive Gregory: an elected candidate's surplus is passed on by
+/// reducing the weight of every ballot on their pile by `surplus / tally`.
+/// When nobody reaches the quota, the lowest candidate is eliminated (ties go
+/// to the first candidate in name order) and their ballots move on at their
+/// current weight.
+fn gregory(candidates: Vec<Candidate>, preferences: Vec<(Vec<Candidate>, usize)>, seats: usize) -> TransferableVoteResult {
+    let total: usize = preferences.iter().map(|(_, weight)| weight).sum();
+    let quota = droop_quota(total, seats) as f64;
+    let mut hopeful: Set<Candidate> = candidates.into_iter().collect();
+    let mut parcels: Vec<Parcel> = preferences.into_iter()
+        .map(|(preferences, weight)| Parcel { preferences, weight: weight as f64, holder: None })
+        .collect();
+    for parcel in parcels.iter_mut() {
+        parcel.holder = parcel.next_holder(&hopeful);
+    }
+
+    let mut elected: Vec<Candidate> = Vec::new();
+    let mut retained: Map<Candidate, f64> = Map::new();
+    let mut pending_surpluses: VecDeque<Candidate> = VecDeque::new();
+    let mut stages = Vec::new();
+
+    loop {
+        let mut tallies: Map<Candidate, f64> = hopeful.iter()
+            .chain(pending_surpluses.iter())
+            .map(|candidate| (candidate.clone(), 0.0))
+            .collect();
+        for parcel in &parcels {
+            if let Some(tally) = parcel.holder.as_ref().and_then(|holder| tallies.get_mut(holder)) {
+                *tally += parcel.weight;
+            }
+        }
+        for (candidate, kept) in &retained {
+            tallies.insert(candidate.clone(), *kept);
+        }
+
+        let mut newly_elected: Vec<Candidate> = hopeful.iter()
+            .filter(|candidate| tallies[*candidate] >= quota)
+            .cloned()
+            .collect();
+        newly_elected.sort_by(|a, b| tallies[b].total_cmp(&tallies[a]));
+        newly_elected.truncate(seats - elected.len());
+        for candidate in &newly_elected {
+            hopeful.remove(candidate);
+            elected.push(candidate.clone());
+            pending_surpluses.push_back(candidate.clone());
+        }
+
+        let mut stage = Stage {
+            tallies: tallies.clone(),
+            elected: newly_elected,
+            eliminated: Vec::new(),
+            surplus_of: None,
+            transfers: Map::new(),
+            exhausted: 0.0,
+        };
+
+        if elected.len() + hopeful.len() <= seats {
+            stage.elected.extend(hopeful.iter().cloned());
+            elected.extend(hopeful.iter().cloned());
+            stages.push(stage);
+            break;
+        }
+        if elected.len() == seats {
+            stages.push(stage);
+            break;
+        }
+
+        let (source, ratio) = match pending_surpluses.pop_front() {
+            Some(candidate) => {
+                let tally = tallies[&candidate];
+                retained.insert(candidate.clone(), tally.min(quota));
+                stage.surplus_of = Some(candidate.clone());
+                let ratio = if tally > quota { (tally - quota) / tally } else { 0.0 };
+                (candidate, ratio)
+            }
+            None => {
+                let lowest = hopeful.iter()
+                    .min_by(|a, b| tallies[*a].total_cmp(&tallies[*b]))
+                    .cloned()
+                    .expect("more hopeful candidates than seats left");
+                hopeful.remove(&lowest);
+                stage.eliminated.push(lowest.clone());
+                (lowest, 1.0)
+            }
+        };
+
+        for parcel in parcels.iter_mut().filter(|parcel| parcel.holder.as_ref() == Some(&source)) {
+            parcel.weight *= ratio;
+            parcel.holder = parcel.next_holder(&hopeful);
+            match &parcel.holder {
+                Some(next) => *stage.transfers.entry(next.clone()).or_insert(0.0) += parcel.weight,
+                None => stage.exhausted += parcel.weight,
+            }
+        }
+        stages.push(stage);
+    }
+
+    TransferableVoteResult { quota, elected, stages }
+}
+
+/// Meek's method: every candidate has a keep value, the share of a ballot's
+/// remaining weight they retain before passing the rest to the next
+/// preference. Keep values of elected candidates are lowered until each of
+/// them holds exactly the quota, which is recomputed from the non-exhausted
+/// votes at every iteration. A stage ends with new elections or, failing
+/// that, the elimination of the lowest hopeful candidate.
+fn meek(candidates: Vec<Candidate>, preferences: Vec<(Vec<Candidate>, usize)>, seats: usize) -> TransferableVoteResult {
+    let total: usize = preferences.iter().map(|(_, weight)| weight).sum();
+    let mut keep: Map<Candidate, f64> = candidates.iter().map(|candidate| (candidate.clone(), 1.0)).collect();
+    let mut hopeful: Set<Candidate> = candidates.into_iter().collect();
+    let mut elected: Vec<Candidate> = Vec::new();
+    let mut stages: Vec<Stage> = Vec::new();
+    let mut quota = 0.0;
+    let mut previous: Map<Candidate, f64> = Map::new();
+
+    loop {
+        let (mut tallies, mut exhausted) = meek_distribute(&preferences, &keep);
+        for _ in 0..MEEK_MAX_ITERATIONS {
+            quota = (total as f64 - exhausted) / (seats + 1) as f64;
+            let mut converged = true;
+            for candidate in &elected {
+                let tally = tallies[candidate];
+                if tally > 0.0 && (tally - quota).abs() > MEEK_TOLERANCE {
+                    converged = false;
+                    let value = keep[candidate] * quota / tally;
+                    keep.insert(candidate.clone(), value.min(1.0));
+                }
+            }
+            if converged {
+                break;
+            }
+            (tallies, exhausted) = meek_distribute(&preferences, &keep);
+        }
+
+        let mut newly_elected: Vec<Candidate> = hopeful.iter()
+            .filter(|candidate| tallies[*candidate] > quota + MEEK_TOLERANCE)
+            .cloned()
+            .collect();
+        newly_elected.sort_by(|a, b| tallies[b].total_cmp(&tallies[a]));
+        newly_elected.truncate(seats - elected.len());
+        for candidate in &newly_elected {
+            hopeful.remove(candidate);
+            elected.push(candidate.clone());
+        }
+
+        let transfers = if stages.is_empty() {
+            Map::new()
+        } else {
+            tallies.iter()
+                .map(|(candidate, tally)| (candidate.clone(), tally - previous[candidate]))
+                .filter(|(_, gain)| *gain > MEEK_TOLERANCE)
+                .collect()
+        };
+        let mut stage = Stage {
+            tallies: tallies.clone(),
+            elected: newly_elected,
+            eliminated: Vec::new(),
+            surplus_of: None,
+            transfers,
+            exhausted,
+        };
+        previous = tallies.clone();
+
+        if elected.len() + hopeful.len() <= seats {
+            stage.elected.extend(hopeful.iter().cloned());
+            elected.extend(hopeful.iter().cloned());
+            stages.push(stage);
+            break;
+        }
+        if elected.len() == seats {
+            stages.push(stage);
+            break;
+        }
+
+        if stage.elected.is_empty() {
+            let lowest = hopeful.iter()
+                .min_by(|a, b| tallies[*a].total_cmp(&tallies[*b]))
+                .cloned()
+                .expect("more hopeful candidates than seats left");
+            hopeful.remove(&lowest);
+            keep.insert(lowest.clone(), 0.0);
+            stage.eliminated.push(lowest);
+        }
+        stages.push(stage);
+    }
+
+    TransferableVoteResult { quota, elected, stages }
+}
+
+fn meek_distribute(preferences: &[(Vec<Candidate>, usize)], keep: &Map<Candidate, f64>) -> (Map<Candidate, f64>, f64) {
+    let mut tallies: Map<Candidate, f64> = keep.keys().map(|candidate| (candidate.clone(), 0.0)).collect();
+    let mut exhausted = 0.0;
+    for (ranking, ballot_weight) in preferences {
+        let mut weight = *ballot_weight as f64;
+        for candidate in ranking {
+            let Some(value) = keep.get(candidate) else {
+                continue;
+            };
+            *tallies.get_mut(candidate).unwrap() += weight * value;
+            weight *= 1.0 - value;
+        }
+        exhausted += weight;
+    }
+    (tallies, exhausted)
+}
+
+/// Multi-seat election over ranked ballots. The scoreboard only holds first
+/// preferences; the stages are computed from the ballot box on demand.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SingleTransferableVote {
+    pub seats: usize,
+    pub surplus: SurplusTransfer,
+}
+
+impl TallyRule for SingleTransferableVote {
+    fn name(&self) -> &str {
+        "single-transferable-vote"
+    }
+
+    fn ballot_kind(&self) -> BallotKind {
+        BallotKind::Ranked
+    }
+
+    fn count(&self, voter: Voter, ballot: &Ballot, scoreboard: &mut Scoreboard) -> VoteOutcome {
+        match ballot {
+            Ballot::Ranked(ranking) => count_ranking(voter, ranking, scoreboard, |position| usize::from(position == 0)),
+            _ => count_invalid(voter, scoreboard),
+        }
+    }
+
+    fn options(&self) -> TallyOptions {
+        TallyOptions { seats: self.seats, surplus: self.surplus, ..TallyOptions::default() }
+    }
+
+    fn report(&self, scoreboard: &Scoreboard, ballots: &BallotBox) -> Option<TallyReport> {
+        let candidates = scoreboard.scores.keys().cloned().collect();
+        Some(TallyReport::TransferableVote(single_transferable_vote(candidates, ballots, self.seats, self.surplus)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn candidate(name: &str) -> Candidate {
+        Candidate(name.to_string())
+    }
+
+    fn ballots(groups: &[(usize, &[&str])]) -> BallotBox {
+        let mut ballots = Vec::new();
+        for (count, ranking) in groups {
+            for _ in 0..*count {
+                ballots.push(Ballot::Ranked(ranking.iter().map(|name| candidate(name)).collect()));
+            }
+        }
+        BallotBox::from(ballots)
+    }
+
+    fn candidates() -> Vec<Candidate> {
+        vec![candidate("Alice"), candidate("Bob"), candidate("Charlie"), candidate("Dana")]
+    }
+
+    fn election() -> BallotBox {
+        ballots(&[
+            (6, &["Alice", "Bob"]),
+            (2, &["Bob", "Alice"]),
+            (4, &["Charlie", "Dana"]),
+            (1, &["Dana", "Charlie"]),
+        ])
+    }
+
+    #[test]
+    fn droop_quota_for_two_seats() {
+        assert_eq!(droop_quota(12, 2), 5);
+    }
+
+    #[test]
+    fn gregory_transfers_surplus_then_eliminates() {
+        let result = single_transferable_vote(candidates(), &election(), 2, SurplusTransfer::Gregory);
+
+        assert_eq!(result.quota, 5.0);
+        assert_eq!(result.elected, vec![candidate("Alice"), candidate("Charlie")]);
+
+        let first = &result.stages[0];
+        assert_eq!(first.elected, vec![candidate("Alice")]);
+        assert_eq!(first.surplus_of, Some(candidate("Alice")));
+        assert!((first.transfers[&candidate("Bob")] - 1.0).abs() < 1e-9);
+        assert!(result.stages.iter().any(|stage| stage.eliminated == vec![candidate("Dana")]));
+    }
+
+    #[test]
+    fn meek_elects_the_same_candidates() {
+        let result = single_transferable_vote(candidates(), &election(), 2, SurplusTransfer::Meek);
+
+        assert_eq!(result.elected, vec![candidate("Alice"), candidate("Charlie")]);
+        assert!((result.quota - 13.0 / 3.0).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,359 @@
+use std::any::Any;
+use std::collections::BTreeMap as Map;
+use std::collections::BTreeSet as Set;
+use std::fmt::Debug;
+use std::sync::Arc;
+
+use anyhow::anyhow;
+
+use crate::domain::*;
+use crate::tallies::approval::Approval;
+use crate::tallies::cumulative::Cumulative;
+use crate::tallies::instant_runoff::InstantRunoff;
+use crate::tallies::majority_judgment::{GradeScale, JudgedCandidate, MajorityJudgment};
+use crate::tallies::plurality::{Plurality, TwoRound};
+use crate::tallies::quadratic::{Quadratic, QuadraticResult};
+use crate::tallies::positional::{Positional, PositionalRule};
+use crate::tallies::score::{ScoreRange, ScoreVoting, Star, StarResult};
+use crate::tallies::single_transferable_vote::{SingleTransferableVote, SurplusTransfer, TransferableVoteResult};
+
+/// Shape of the ballots a rule counts, so that the interface knows what to
+/// ask the voter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BallotKind {
+    Single,
+    Ranked,
+    Approval,
+    Scored,
+    /// Points spread over the candidates within a budget.
+    Points,
+    /// Votes bought with credits, `k` votes for a candidate costing `k²`.
+    Credits,
+    Graded,
+}
+
+/// Parameters the counting rules are built from. They come from the
+/// configuration and are persisted with the machine so that its rule can be
+/// rebuilt when it is loaded again.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TallyOptions {
+    pub seats: usize,
+    pub surplus: SurplusTransfer,
+    pub weights: Vec<usize>,
+    pub score_range: ScoreRange,
+    pub grades: GradeScale,
+    /// Points or credits each voter may spend on their ballot.
+    pub budget: usize,
+}
+
+impl Default for TallyOptions {
+    fn default() -> Self {
+        TallyOptions {
+            seats: 1,
+            surplus: SurplusTransfer::Gregory,
+            weights: Vec::new(),
+            score_range: ScoreRange { min: 0, max: 5 },
+            grades: GradeScale(Vec::new()),
+            budget: 5,
+        }
+    }
+}
+
+/// Counting rule of an election. `VotingMachine` checks attendance and keeps
+/// the ballot box; the rule decides whether a ballot is valid and how it
+/// moves the scoreboard.
+pub trait TallyRule: Any + Debug + Send + Sync {
+    /// Name the rule is selected and persisted under.
+    fn name(&self) -> &str;
+
+    fn ballot_kind(&self) -> BallotKind;
+
+    /// Checks the ballot and records it on the scoreboard, blank and invalid
+    /// ballots included. A ballot of another shape than `ballot_kind` is
+    /// invalid.
+    fn count(&self, voter: Voter, ballot: &Ballot, scoreboard: &mut Scoreboard) -> VoteOutcome;
+
+    /// Refreshes the results computed from the whole ballot box, once a new
+    /// ballot has been counted.
+    fn tally(&self, _ballots: &BallotBox, _scoreboard: &mut Scoreboard) {}
+
+    /// Whether closing a first round without an absolute majority opens a
+    /// second round between the two leading candidates.
+    fn has_second_round(&self) -> bool {
+        false
+    }
+
+    /// Points or credits each voter may spend, for the rules that limit them.
+    fn budget(&self) -> Option<usize> {
+        None
+    }
+
+    /// Share of the budget an accepted ballot spends, `usize::MAX` when it
+    /// cannot be represented, which no budget covers.
+    fn cost(&self, _ballot: &Ballot) -> usize {
+        0
+    }
+
+    /// Parameters to persist so that `build_rule` can rebuild this rule.
+    fn options(&self) -> TallyOptions {
+        TallyOptions::default()
+    }
+
+    /// Whether the scores are points ranking the candidates rather than
+    /// votes.
+    fn awards_points(&self) -> bool {
+        false
+    }
+
+    /// Results the rule computes from the whole ballot box, beyond the
+    /// scoreboard.
+    fn report(&self, _scoreboard: &Scoreboard, _ballots: &BallotBox) -> Option<TallyReport> {
+        None
+    }
+}
+
+/// Results of a count beyond the scoreboard, as reported by its rule.
+#[derive(Debug, Clone)]
+pub enum TallyReport {
+    TransferableVote(TransferableVoteResult),
+    Star(StarResult),
+    MajorityJudgment(Vec<JudgedCandidate>),
+    Quadratic(QuadraticResult),
+    /// Lines to display as they are, for the rules defined outside this
+    /// crate.
+    Lines(Vec<String>),
+}
+
+pub type RuleFactory = fn(&TallyOptions) -> Arc<dyn TallyRule>;
+
+/// Rules defined outside this crate, selectable by name from the
+/// configuration as well as when a persisted machine is loaded.
+#[derive(Debug, Clone, Default)]
+pub struct RuleRegistry(Map<String, RuleFactory>);
+
+impl RuleRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_rule(mut self, name: &str, factory: RuleFactory) -> Self {
+        self.0.insert(name.to_string(), factory);
+        self
+    }
+}
+
+pub fn build_rule(name: &str, options: &TallyOptions, rules: &RuleRegistry) -> anyhow::Result<Arc<dyn TallyRule>> {
+    let rule: Arc<dyn TallyRule> = match name {
+        "plurality" => Arc::new(Plurality),
+        "two-round" => Arc::new(TwoRound),
+        "approval" => Arc::new(Approval),
+        "instant-runoff" => Arc::new(InstantRunoff),
+        "single-transferable-vote" => Arc::new(SingleTransferableVote { seats: options.seats, surplus: options.surplus }),
+        "borda" => Arc::new(Positional(PositionalRule::Borda)),
+        "dowdall" => Arc::new(Positional(PositionalRule::Dowdall)),
+        "positional" if options.weights.is_empty() => return Err(anyhow!("Le décompte positionnel demande au moins un poids")),
+        "positional" => Arc::new(Positional(PositionalRule::Custom(options.weights.clone()))),
+        "score" => Arc::new(ScoreVoting { range: options.score_range }),
+        "star" => Arc::new(Star { range: options.score_range }),
+        "cumulative" => Arc::new(Cumulative { budget: options.budget }),
+        "quadratic" => Arc::new(Quadratic { credits: options.budget }),
+        "majority-judgment" if options.grades.0.is_empty() => return Err(anyhow!("Le jugement majoritaire demande au moins une mention")),
+        "majority-judgment" => Arc::new(MajorityJudgment { scale: options.grades.clone() }),
+        _ => {
+            let factory = rules.0.get(name).ok_or_else(|| anyhow!("Règle de dépouillement inconnue : {}", name))?;
+            factory(options)
+        }
+    };
+    Ok(rule)
+}
+
+pub fn count_blank(voter: Voter, scoreboard: &mut Scoreboard) -> VoteOutcome {
+    scoreboard.blank_score.0 += 1;
+    VoteOutcome::BlankVote(voter)
+}
+
+pub fn count_invalid(voter: Voter, scoreboard: &mut Scoreboard) -> VoteOutcome {
+    scoreboard.invalid_score.0 += 1;
+    VoteOutcome::InvalidVote(voter)
+}
+
+/// Counts one vote for the chosen candidate, a blank vote when there is none.
+pub fn count_choice(voter: Voter, choice: Option<&Candidate>, scoreboard: &mut Scoreboard) -> VoteOutcome {
+    let Some(candidate) = choice else {
+        return count_blank(voter, scoreboard);
+    };
+    match scoreboard.scores.get_mut(candidate) {
+        Some(score) => {
+            score.0 += 1;
+            VoteOutcome::AcceptedVote(voter, candidate.clone())
+        }
+        None => count_invalid(voter, scoreboard),
+    }
+}
+
+/// Counts a ranking of distinct, known candidates, giving each one
+/// `points(position)`.
+pub fn count_ranking(voter: Voter, ranking: &[Candidate], scoreboard: &mut Scoreboard, points: impl Fn(usize) -> usize) -> VoteOutcome {
+    if ranking.is_empty() {
+        return count_blank(voter, scoreboard);
+    }
+
+    let distinct: Set<&Candidate> = ranking.iter().collect();
+    let all_known = ranking.iter().all(|candidate| scoreboard.scores.contains_key(candidate));
+    if !all_known || distinct.len() != ranking.len() {
+        return count_invalid(voter, scoreboard);
+    }
+
+    for (position, candidate) in ranking.iter().enumerate() {
+        if let Some(score) = scoreboard.scores.get_mut(candidate) {
+            score.0 += points(position);
+        }
+    }
+    VoteOutcome::AcceptedRanking(voter, ranking.to_vec())
+}
+
+/// Counts one vote per approved candidate. Unknown candidates are ignored as
+/// long as at least one approved candidate is running.
+pub fn count_approval(voter: Voter, approved: &Set<Candidate>, scoreboard: &mut Scoreboard) -> VoteOutcome {
+    if approved.is_empty() {
+        return count_blank(voter, scoreboard);
+    }
+
+    let (known, unknown): (Vec<Candidate>, Vec<Candidate>) = approved.iter().cloned()
+        .partition(|candidate| scoreboard.scores.contains_key(candidate));
+    if known.is_empty() {
+        return count_invalid(voter, scoreboard);
+    }
+
+    for candidate in &known {
+        if let Some(score) = scoreboard.scores.get_mut(candidate) {
+            score.0 += 1;
+        }
+    }
+    if unknown.is_empty() {
+        VoteOutcome::AcceptedApproval(voter, known)
+    } else {
+        VoteOutcome::PartiallyInvalidVote(voter, unknown)
+    }
+}
+
+/// Adds the scores given to known candidates, all of them within `range` when
+/// there is one. Totals saturate rather than wrap.
+pub fn count_scores(voter: Voter, scores: &Map<Candidate, usize>, scoreboard: &mut Scoreboard, range: Option<ScoreRange>) -> VoteOutcome {
+    if scores.is_empty() {
+        return count_blank(voter, scoreboard);
+    }
+
+    let valid = scores.iter().all(|(candidate, score)| {
+        scoreboard.scores.contains_key(candidate) && range.is_none_or(|range| range.contains(*score))
+    });
+    if !valid {
+        return count_invalid(voter, scoreboard);
+    }
+
+    for (candidate, points) in scores {
+        if let Some(score) = scoreboard.scores.get_mut(candidate) {
+            score.0 = score.0.saturating_add(*points);
+        }
+    }
+    VoteOutcome::AcceptedScores(voter, scores.clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Counts every ballot, whatever its contents, for the first candidate.
+    #[derive(Debug)]
+    struct FirstCandidate;
+
+    impl TallyRule for FirstCandidate {
+        fn name(&self) -> &str {
+            "first-candidate"
+        }
+
+        fn ballot_kind(&self) -> BallotKind {
+            BallotKind::Single
+        }
+
+        fn count(&self, voter: Voter, _ballot: &Ballot, scoreboard: &mut Scoreboard) -> VoteOutcome {
+            let (candidate, score) = scoreboard.scores.iter_mut().next().unwrap();
+            score.0 += 1;
+            VoteOutcome::AcceptedVote(voter, candidate.clone())
+        }
+
+        fn report(&self, scoreboard: &Scoreboard, _ballots: &BallotBox) -> Option<TallyReport> {
+            let candidate = scoreboard.scores.keys().next()?;
+            Some(TallyReport::Lines(vec![format!("Premier candidat : {}", candidate.0)]))
+        }
+    }
+
+    #[test]
+    fn builds_builtin_rules() {
+        let options = TallyOptions { seats: 3, ..TallyOptions::default() };
+
+        let rules = RuleRegistry::new();
+
+        assert_eq!(build_rule("plurality", &options, &rules).unwrap().name(), "plurality");
+        assert_eq!(build_rule("single-transferable-vote", &options, &rules).unwrap().options().seats, 3);
+        assert!(build_rule("unknown", &options, &rules).is_err());
+        assert!(build_rule("majority-judgment", &options, &rules).is_err());
+    }
+
+    #[test]
+    fn positional_rules_need_weights() {
+        let rules = RuleRegistry::new();
+
+        assert!(build_rule("positional", &TallyOptions::default(), &rules).is_err());
+        let options = TallyOptions { weights: vec![2, 1], ..TallyOptions::default() };
+        assert_eq!(build_rule("positional", &options, &rules).unwrap().options().weights, vec![2, 1]);
+    }
+
+    #[test]
+    fn rules_refuse_ballots_of_another_shape() {
+        let alice = Candidate("Alice".to_string());
+        let ballots = [
+            (BallotKind::Single, Ballot::Single(Some(alice.clone()))),
+            (BallotKind::Ranked, Ballot::Ranked(vec![alice.clone()])),
+            (BallotKind::Approval, Ballot::Approval(Set::from([alice.clone()]))),
+            (BallotKind::Scored, Ballot::Scored(Map::from([(alice.clone(), 1000)]))),
+            (BallotKind::Graded, Ballot::Graded(Map::from([(alice.clone(), Grade("Bien".to_string()))]))),
+        ];
+        let options = TallyOptions { grades: GradeScale(vec![Grade("Bien".to_string())]), ..TallyOptions::default() };
+
+        for name in ["plurality", "two-round", "approval", "instant-runoff", "single-transferable-vote", "borda", "score", "star", "cumulative", "quadratic", "majority-judgment"] {
+            let rule = build_rule(name, &options, &RuleRegistry::new()).unwrap();
+            let accepted_kind = match rule.ballot_kind() {
+                BallotKind::Points | BallotKind::Credits => BallotKind::Scored,
+                kind => kind,
+            };
+            for (kind, ballot) in ballots.iter().filter(|(kind, _)| *kind != accepted_kind) {
+                let mut scoreboard = Scoreboard::new(vec![alice.clone()]);
+
+                let outcome = rule.count(Voter("Claude".to_string()), ballot, &mut scoreboard);
+
+                assert!(matches!(outcome, VoteOutcome::InvalidVote(_)), "{} accepted a {:?} ballot", name, kind);
+                assert_eq!(scoreboard.scores[&alice].0, 0);
+            }
+        }
+    }
+
+    #[test]
+    fn registered_rules_drive_the_machine() {
+        let rules = RuleRegistry::new().with_rule("first-candidate", |_| Arc::new(FirstCandidate));
+        let rule = build_rule("first-candidate", &TallyOptions::default(), &rules).unwrap();
+        let candidates = vec![Candidate("Alice".to_string()), Candidate("Bob".to_string())];
+        let mut voting_machine = VotingMachine::new(Scoreboard::new(candidates)).with_rule(rule);
+
+        let outcome = voting_machine.vote(BallotPaper {
+            voter: Voter("Claude".to_string()),
+            ballot: Ballot::Single(Some(Candidate("Bob".to_string()))),
+            code: None,
+        });
+
+        assert!(matches!(outcome, VoteOutcome::AcceptedVote(_, candidate) if candidate.0 == "Alice"));
+        assert_eq!(voting_machine.get_rule().name(), "first-candidate");
+        assert!(build_rule("first-candidate", &TallyOptions::default(), &RuleRegistry::new()).is_err());
+        assert!(matches!(voting_machine.report(), Some(TallyReport::Lines(lines)) if lines == ["Premier candidat : Alice"]));
+    }
+}
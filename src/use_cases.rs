@@ -1,58 +1,392 @@
+use std::collections::BTreeMap as Map;
+
 use serde::Deserialize;
 
+use std::sync::Arc;
+
+use crate::clock::{Clock, SystemClock};
 use crate::{domain::*, storage::*};
+use crate::tallies::condorcet::{self, PairwiseMatrix};
+use crate::tallies::proportional::{allocate_seats, ProportionalRule, SeatAllocation};
+use crate::tally::TallyReport;
 
 #[derive(Deserialize)]
 pub struct VoteForm {
     pub voter: String,
-    pub candidate: String
+    pub candidate: String,
+    /// Voting code, left empty when the election issued none.
+    #[serde(default)]
+    pub code: String,
+}
+
+#[derive(Deserialize)]
+pub struct RankedVoteForm {
+    pub voter: String,
+    pub ranking: Vec<String>,
+    #[serde(default)]
+    pub code: String,
+}
+
+#[derive(Deserialize)]
+pub struct ApprovalVoteForm {
+    pub voter: String,
+    pub candidates: Vec<String>,
+    #[serde(default)]
+    pub code: String,
+}
+
+#[derive(Deserialize)]
+pub struct ScoreVoteForm {
+    pub voter: String,
+    pub scores: Map<String, usize>,
+    #[serde(default)]
+    pub code: String,
+}
+
+#[derive(Deserialize)]
+pub struct GradeVoteForm {
+    pub voter: String,
+    pub grades: Map<String, String>,
+    #[serde(default)]
+    pub code: String,
+}
+
+#[derive(Deserialize)]
+pub struct DelegationForm {
+    pub voter: String,
+    pub delegate: String,
+    #[serde(default)]
+    pub code: String,
+}
+
+#[derive(Deserialize)]
+pub struct ProxyForm {
+    pub principal: String,
+    pub holder: String,
 }
 
 pub struct VotingController<Store> {
     store: Store,
+    election: ElectionId,
+    clock: Arc<dyn Clock>,
 }
 
 impl From<VoteForm> for BallotPaper {
     fn from(voteform: VoteForm) -> Self {
         Self {
             voter: Voter(voteform.voter),
-            candidate: if voteform.candidate.is_empty(){
+            ballot: Ballot::Single(if voteform.candidate.is_empty(){
                 None
             } else {
                 Some(Candidate(voteform.candidate))
-            }
+            }),
+            code: voting_code(voteform.code),
+        }
+    }
+}
+
+impl From<RankedVoteForm> for BallotPaper {
+    fn from(voteform: RankedVoteForm) -> Self {
+        Self {
+            voter: Voter(voteform.voter),
+            ballot: Ballot::Ranked(voteform.ranking.into_iter().map(Candidate).collect()),
+            code: voting_code(voteform.code),
+        }
+    }
+}
+
+impl From<ApprovalVoteForm> for BallotPaper {
+    fn from(voteform: ApprovalVoteForm) -> Self {
+        Self {
+            voter: Voter(voteform.voter),
+            ballot: Ballot::Approval(voteform.candidates.into_iter().map(Candidate).collect()),
+            code: voting_code(voteform.code),
         }
     }
 }
 
+impl From<ScoreVoteForm> for BallotPaper {
+    fn from(voteform: ScoreVoteForm) -> Self {
+        Self {
+            voter: Voter(voteform.voter),
+            ballot: Ballot::Scored(voteform.scores.into_iter().map(|(candidate, score)| (Candidate(candidate), score)).collect()),
+            code: voting_code(voteform.code),
+        }
+    }
+}
+
+impl From<GradeVoteForm> for BallotPaper {
+    fn from(voteform: GradeVoteForm) -> Self {
+        Self {
+            voter: Voter(voteform.voter),
+            ballot: Ballot::Graded(voteform.grades.into_iter().map(|(candidate, grade)| (Candidate(candidate), Grade(grade))).collect()),
+            code: voting_code(voteform.code),
+        }
+    }
+}
+
+impl From<DelegationForm> for BallotPaper {
+    fn from(delegationform: DelegationForm) -> Self {
+        Self {
+            voter: Voter(delegationform.voter),
+            ballot: Ballot::Delegated(Voter(delegationform.delegate)),
+            code: voting_code(delegationform.code),
+        }
+    }
+}
+
+/// Code typed on a form, `None` when left empty.
+fn voting_code(code: String) -> Option<VotingCode> {
+    if code.trim().is_empty() {
+        None
+    } else {
+        Some(VotingCode(code))
+    }
+}
+
 impl <Store: Storage> VotingController<Store> {
     pub fn new(store: Store) -> Self{
         Self {
-            store
+            store,
+            election: ElectionId::default(),
+            clock: Arc::new(SystemClock),
+        }
+    }
+
+    /// Clock the scheduled openings and closings are checked against.
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Current election, moved along its schedule.
+    async fn load(&self) -> anyhow::Result<VotingMachine> {
+        let mut voting_machine = self.store.get_voting_machine(&self.election).await?.with_clock(self.clock.clone());
+        voting_machine.follow_schedule();
+        Ok(voting_machine)
+    }
+
+    /// Election the other operations act on.
+    pub fn current_election(&self) -> &ElectionId {
+        &self.election
+    }
+
+    pub async fn select_election(&mut self, election: &str) -> anyhow::Result<()> {
+        let election = ElectionId(election.to_string());
+        self.store.get_voting_machine(&election).await?;
+        self.election = election;
+        Ok(())
+    }
+
+    pub async fn election_ids(&self) -> anyhow::Result<Vec<ElectionId>> {
+        Ok(self.store.get_elections().await?.0.into_keys().collect())
+    }
+
+    pub async fn election_states(&self) -> anyhow::Result<Map<ElectionId, ElectionState>> {
+        Ok(self.store.get_elections().await?.0.into_iter()
+            .map(|(id, machine)| {
+                let mut machine = machine.with_clock(self.clock.clone());
+                machine.follow_schedule();
+                (id, machine.get_state())
+            })
+            .collect())
+    }
+
+    pub async fn add_election(&mut self, election: &str, voting_machine: VotingMachine) -> anyhow::Result<()> {
+        let election = ElectionId(election.to_string());
+        if self.store.get_elections().await?.0.contains_key(&election) {
+            return Err(anyhow::anyhow!("Le scrutin {} existe déjà", election.0));
         }
+        self.store.put_voting_machine(&election, voting_machine).await
     }
 
-    pub async fn vote(&mut self, vote_form: VoteForm) -> anyhow::Result<VoteOutcome> {
+    pub async fn vote<Form: Into<BallotPaper>>(&mut self, vote_form: Form) -> anyhow::Result<VoteOutcome> {
         let ballot_paper: BallotPaper = vote_form.into();
         
-        let mut voting_machine = self.store.get_voting_machine().await?;
+        let mut voting_machine = self.load().await?;
         
         let outcome = voting_machine.vote(ballot_paper);
         
-        self.store.put_voting_machine(voting_machine).await?;
+        self.store.put_voting_machine(&self.election, voting_machine).await?;
         
         Ok(outcome)
     }
 
+    pub async fn grant_proxy(&mut self, proxy_form: ProxyForm) -> anyhow::Result<VoteOutcome> {
+        let mut voting_machine = self.load().await?;
+
+        let outcome = voting_machine.grant_proxy(Voter(proxy_form.principal), Voter(proxy_form.holder));
+
+        self.store.put_voting_machine(&self.election, voting_machine).await?;
+
+        Ok(outcome)
+    }
+
+    /// Casts the ballot of the form's voter on behalf of their proxy `holder`.
+    pub async fn vote_by_proxy<Form: Into<BallotPaper>>(&mut self, holder: &str, vote_form: Form) -> anyhow::Result<VoteOutcome> {
+        let mut voting_machine = self.load().await?;
+
+        let outcome = voting_machine.vote_by_proxy(Voter(holder.to_string()), vote_form.into());
+
+        self.store.put_voting_machine(&self.election, voting_machine).await?;
+
+        Ok(outcome)
+    }
+
+    pub async fn close_round(&mut self) -> anyhow::Result<RoundOutcome> {
+        let mut voting_machine = self.load().await?;
+
+        let outcome = voting_machine.close_round();
+
+        self.store.put_voting_machine(&self.election, voting_machine).await?;
+
+        Ok(outcome)
+    }
+
+    pub async fn add_candidate(&mut self, candidate: &str) -> anyhow::Result<CandidateOutcome> {
+        let mut voting_machine = self.load().await?;
+
+        let outcome = voting_machine.add_candidate(Candidate(candidate.to_string()));
+
+        self.store.put_voting_machine(&self.election, voting_machine).await?;
+
+        Ok(outcome)
+    }
+
+    pub async fn withdraw_candidate(&mut self, candidate: &str, policy: WithdrawalPolicy) -> anyhow::Result<CandidateOutcome> {
+        let mut voting_machine = self.load().await?;
+
+        let outcome = voting_machine.withdraw_candidate(Candidate(candidate.to_string()), policy);
+
+        self.store.put_voting_machine(&self.election, voting_machine).await?;
+
+        Ok(outcome)
+    }
+
+    pub async fn rename_candidate(&mut self, from: &str, to: &str) -> anyhow::Result<CandidateOutcome> {
+        let mut voting_machine = self.load().await?;
+
+        let outcome = voting_machine.rename_candidate(Candidate(from.to_string()), Candidate(to.to_string()));
+
+        self.store.put_voting_machine(&self.election, voting_machine).await?;
+
+        Ok(outcome)
+    }
+
+    /// Counts the votes written in for `from` as written in for `into`, or
+    /// for `into` itself when they are running.
+    pub async fn merge_write_ins(&mut self, from: &str, into: &str) -> anyhow::Result<CandidateOutcome> {
+        let mut voting_machine = self.load().await?;
+
+        let outcome = voting_machine.merge_write_ins(&Candidate(from.to_string()), &Candidate(into.to_string()));
+
+        self.store.put_voting_machine(&self.election, voting_machine).await?;
+
+        Ok(outcome)
+    }
+
+    /// Makes a name written in on the ballots a candidate.
+    pub async fn promote_write_in(&mut self, name: &str) -> anyhow::Result<CandidateOutcome> {
+        let mut voting_machine = self.load().await?;
+
+        let outcome = voting_machine.promote_write_in(&Candidate(name.to_string()));
+
+        self.store.put_voting_machine(&self.election, voting_machine).await?;
+
+        Ok(outcome)
+    }
+
+    /// Moves the current election to `state`.
+    pub async fn transition(&mut self, state: ElectionState) -> anyhow::Result<TransitionOutcome> {
+        let mut voting_machine = self.load().await?;
+
+        let outcome = voting_machine.transition(state);
+
+        self.store.put_voting_machine(&self.election, voting_machine).await?;
+
+        Ok(outcome)
+    }
+
     pub async fn get_voting_machine(&self) -> anyhow::Result<VotingMachine> {
-        self.store.get_voting_machine().await
+        self.load().await
+    }
+
+    /// Current election, refused while its results are hidden.
+    async fn results_machine(&self) -> anyhow::Result<VotingMachine> {
+        let voting_machine = self.load().await?;
+        if !voting_machine.results_visible() {
+            return Err(anyhow::anyhow!("Les résultats seront publiés à la clôture du scrutin"));
+        }
+        Ok(voting_machine)
+    }
+
+    /// Results the counting rule computes beyond the scoreboard.
+    pub async fn report(&self) -> anyhow::Result<Option<TallyReport>> {
+        Ok(self.results_machine().await?.report())
+    }
+
+    pub async fn remaining_credits(&self, voter: &str) -> anyhow::Result<Option<usize>> {
+        Ok(self.load().await?.remaining_credits(&Voter(voter.to_string())))
+    }
+
+    /// Issues a fresh code to every voter on the roll of the current
+    /// election, while it is a draft.
+    pub async fn issue_codes(&mut self) -> anyhow::Result<CodesOutcome> {
+        let mut voting_machine = self.load().await?;
+
+        let codes = voting_machine.issue_codes();
+
+        self.store.put_voting_machine(&self.election, voting_machine).await?;
+
+        Ok(codes)
+    }
+
+    pub async fn revisions(&self) -> anyhow::Result<Vec<Revision>> {
+        Ok(self.load().await?.get_revisions().to_vec())
+    }
+
+    pub async fn turnout(&self) -> anyhow::Result<Option<Turnout>> {
+        Ok(self.load().await?.turnout())
+    }
+
+    pub async fn carried_weights(&self) -> anyhow::Result<Map<Voter, usize>> {
+        Ok(self.load().await?.carried_weights())
+    }
+
+    pub async fn allocate_seats(&self, rule: &ProportionalRule) -> anyhow::Result<SeatAllocation> {
+        Ok(allocate_seats(self.results_machine().await?.get_scoreboard(), rule))
+    }
+
+    pub async fn pairwise_preferences(&self) -> anyhow::Result<PairwiseMatrix> {
+        Ok(self.results_machine().await?.pairwise_preferences())
+    }
+
+    pub async fn condorcet_winner(&self) -> anyhow::Result<Option<Candidate>> {
+        Ok(condorcet::condorcet_winner(&self.pairwise_preferences().await?))
+    }
+
+    pub async fn strongest_paths(&self) -> anyhow::Result<PairwiseMatrix> {
+        Ok(condorcet::strongest_paths(&self.pairwise_preferences().await?))
+    }
+
+    pub async fn schulze_ranking(&self) -> anyhow::Result<Vec<Vec<Candidate>>> {
+        Ok(condorcet::schulze_ranking(&self.strongest_paths().await?))
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::clock::{FixedClock, Timestamp};
     use crate::storages::memory::Memory;
+    use crate::tallies::approval::Approval;
+    use crate::tallies::instant_runoff::InstantRunoff;
+    use crate::tallies::proportional::AllocationMethod;
+    use std::sync::Arc;
+    use crate::tallies::quadratic::Quadratic;
+    use crate::tallies::score::{ScoreRange, Star};
+    use crate::tallies::single_transferable_vote::{SingleTransferableVote, SurplusTransfer};
+    use crate::tally::TallyRule;
 
     async fn setup_controller() -> VotingController<Memory> {
         let candidates = vec![
@@ -66,6 +400,15 @@ mod tests {
         VotingController::new(storage)
     }
 
+    async fn setup_controller_with(rule: Arc<dyn TallyRule>) -> VotingController<Memory> {
+        let candidates = vec![
+            Candidate("Alice".to_string()),
+            Candidate("Bob".to_string()),
+        ];
+        let voting_machine = VotingMachine::new(Scoreboard::new(candidates)).with_rule(rule);
+        VotingController::new(Memory::new(voting_machine).await.unwrap())
+    }
+
     #[tokio::test]
     async fn accepted_vote() {
         let mut controller = setup_controller().await;
@@ -73,6 +416,7 @@ mod tests {
         let vote_form = VoteForm {
             voter: String::from("Claude"),
             candidate: String::from("Alice"),
+            code: String::new(),
         };
 
         let result = controller.vote(vote_form).await.unwrap();
@@ -91,6 +435,7 @@ mod tests {
         let vote_form = VoteForm {
             voter: String::from("Claude"),
             candidate: String::from(""),
+            code: String::new(),
         };
 
         let result = controller.vote(vote_form).await.unwrap();
@@ -108,6 +453,7 @@ mod tests {
         let vote_form = VoteForm {
             voter: String::from("Claude"),
             candidate: String::from("Unknown"),
+            code: String::new(),
         };
 
         let result = controller.vote(vote_form).await.unwrap();
@@ -125,6 +471,7 @@ mod tests {
         let vote_form1 = VoteForm {
             voter: String::from("Claude"),
             candidate: String::from("Alice"),
+            code: String::new(),
         };
         let result1 = controller.vote(vote_form1).await.unwrap();
         assert!(matches!(result1, VoteOutcome::AcceptedVote(_, _)));
@@ -132,6 +479,7 @@ mod tests {
         let vote_form2 = VoteForm {
             voter: String::from("Claude"),
             candidate: String::from("Bob"),
+            code: String::new(),
         };
         let result2 = controller.vote(vote_form2).await.unwrap();
         
@@ -140,4 +488,340 @@ mod tests {
             assert_eq!(voter.0, "Claude");
         }
     }
+
+    #[tokio::test]
+    async fn ranked_vote() {
+        let mut controller = setup_controller_with(Arc::new(InstantRunoff)).await;
+
+        let vote_form = RankedVoteForm {
+            voter: String::from("Claude"),
+            ranking: vec![String::from("Bob"), String::from("Alice")],
+            code: String::new(),
+        };
+
+        let result = controller.vote(vote_form).await.unwrap();
+
+        assert!(matches!(result, VoteOutcome::AcceptedRanking(_, _)));
+        if let VoteOutcome::AcceptedRanking(voter, ranking) = result {
+            assert_eq!(voter.0, "Claude");
+            assert_eq!(ranking, vec![Candidate("Bob".to_string()), Candidate("Alice".to_string())]);
+        }
+    }
+
+    #[tokio::test]
+    async fn partially_invalid_approval_vote() {
+        let mut controller = setup_controller_with(Arc::new(Approval)).await;
+
+        let vote_form = ApprovalVoteForm {
+            voter: String::from("Claude"),
+            candidates: vec![String::from("Alice"), String::from("Unknown")],
+            code: String::new(),
+        };
+
+        let result = controller.vote(vote_form).await.unwrap();
+
+        assert!(matches!(result, VoteOutcome::PartiallyInvalidVote(_, _)));
+        let voting_machine = controller.get_voting_machine().await.unwrap();
+        assert_eq!(voting_machine.get_scoreboard().scores.get(&Candidate("Alice".to_string())).unwrap().0, 1);
+    }
+
+    #[tokio::test]
+    async fn condorcet_and_schulze() {
+        let mut controller = setup_controller_with(Arc::new(InstantRunoff)).await;
+
+        for (voter, ranking) in [("Claude", ["Bob", "Alice"]), ("Jane", ["Bob", "Alice"]), ("John", ["Alice", "Bob"])] {
+            let vote_form = RankedVoteForm {
+                voter: String::from(voter),
+                ranking: ranking.iter().map(|c| c.to_string()).collect(),
+                code: String::new(),
+            };
+            controller.vote(vote_form).await.unwrap();
+        }
+
+        assert_eq!(controller.condorcet_winner().await.unwrap(), Some(Candidate("Bob".to_string())));
+        let paths = controller.strongest_paths().await.unwrap();
+        assert_eq!(paths.get(&Candidate("Bob".to_string()), &Candidate("Alice".to_string())), 2);
+        assert_eq!(
+            controller.schulze_ranking().await.unwrap(),
+            vec![vec![Candidate("Bob".to_string())], vec![Candidate("Alice".to_string())]]
+        );
+    }
+
+    #[tokio::test]
+    async fn single_transferable_vote() {
+        let candidates = vec![
+            Candidate("Alice".to_string()),
+            Candidate("Bob".to_string()),
+            Candidate("Charlie".to_string()),
+        ];
+        let voting_machine = VotingMachine::new(Scoreboard::new(candidates))
+            .with_rule(Arc::new(SingleTransferableVote { seats: 2, surplus: SurplusTransfer::Gregory }));
+        let mut controller = VotingController::new(Memory::new(voting_machine).await.unwrap());
+
+        for (voter, ranking) in [("Claude", ["Alice", "Bob"]), ("Jane", ["Alice", "Charlie"]), ("John", ["Charlie", "Bob"])] {
+            let vote_form = RankedVoteForm {
+                voter: String::from(voter),
+                ranking: ranking.iter().map(|c| c.to_string()).collect(),
+                code: String::new(),
+            };
+            controller.vote(vote_form).await.unwrap();
+        }
+
+        let Some(TallyReport::TransferableVote(result)) = controller.report().await.unwrap() else { panic!("no STV result") };
+        assert_eq!(result.elected, vec![Candidate("Alice".to_string()), Candidate("Charlie".to_string())]);
+    }
+
+    #[tokio::test]
+    async fn star_vote() {
+        let candidates = vec![
+            Candidate("Alice".to_string()),
+            Candidate("Bob".to_string()),
+        ];
+        let voting_machine = VotingMachine::new(Scoreboard::new(candidates))
+            .with_rule(Arc::new(Star { range: ScoreRange { min: 0, max: 5 } }));
+        let mut controller = VotingController::new(Memory::new(voting_machine).await.unwrap());
+
+        for (voter, alice, bob) in [("Claude", 5, 0), ("Jane", 3, 4), ("John", 2, 3)] {
+            let vote_form = ScoreVoteForm {
+                voter: String::from(voter),
+                scores: Map::from([(String::from("Alice"), alice), (String::from("Bob"), bob)]),
+                code: String::new(),
+            };
+            let result = controller.vote(vote_form).await.unwrap();
+            assert!(matches!(result, VoteOutcome::AcceptedScores(_, _)));
+        }
+
+        let Some(TallyReport::Star(result)) = controller.report().await.unwrap() else { panic!("no STAR result") };
+        assert_eq!(result.winner, Some(Candidate("Bob".to_string())));
+    }
+
+    #[tokio::test]
+    async fn seat_allocation() {
+        let mut controller = setup_controller().await;
+
+        for (voter, list) in [("Claude", "Alice"), ("Jane", "Alice"), ("John", "Bob")] {
+            let vote_form = VoteForm {
+                voter: String::from(voter),
+                candidate: String::from(list),
+                code: String::new(),
+            };
+            controller.vote(vote_form).await.unwrap();
+        }
+
+        let rule = ProportionalRule { method: AllocationMethod::DHondt, seats: 3, threshold: 0.0 };
+        let allocation = controller.allocate_seats(&rule).await.unwrap();
+        assert_eq!(allocation.seats[&Candidate("Alice".to_string())], 2);
+        assert_eq!(allocation.seats[&Candidate("Bob".to_string())], 1);
+    }
+
+    #[tokio::test]
+    async fn quadratic_credits() {
+        let voting_machine = VotingMachine::new(Scoreboard::new(vec![Candidate("Alice".to_string()), Candidate("Bob".to_string())]))
+            .with_rule(Arc::new(Quadratic { credits: 9 }));
+        let mut controller = VotingController::new(Memory::new(voting_machine).await.unwrap());
+
+        let vote_form = ScoreVoteForm {
+            voter: String::from("Claude"),
+            scores: Map::from([(String::from("Alice"), 2), (String::from("Bob"), 2)]),
+            code: String::new(),
+        };
+        let result = controller.vote(vote_form).await.unwrap();
+        assert!(matches!(result, VoteOutcome::AcceptedScores(_, _)));
+
+        assert_eq!(controller.remaining_credits("Claude").await.unwrap(), Some(1));
+        let Some(TallyReport::Quadratic(result)) = controller.report().await.unwrap() else { panic!("no quadratic result") };
+        assert_eq!(result.votes[&Candidate("Alice".to_string())], 2);
+        assert_eq!(result.credits[&Candidate("Bob".to_string())], 4);
+    }
+
+    #[tokio::test]
+    async fn delegated_vote() {
+        let mut controller = setup_controller().await;
+
+        let delegation_form = DelegationForm {
+            voter: String::from("Claude"),
+            delegate: String::from("Jane"),
+            code: String::new(),
+        };
+        let result = controller.vote(delegation_form).await.unwrap();
+        assert!(matches!(result, VoteOutcome::Delegated(_, _)));
+
+        let vote_form = VoteForm {
+            voter: String::from("Jane"),
+            candidate: String::from("Bob"),
+            code: String::new(),
+        };
+        controller.vote(vote_form).await.unwrap();
+
+        assert_eq!(controller.carried_weights().await.unwrap()[&Voter("Jane".to_string())], 2);
+        let voting_machine = controller.get_voting_machine().await.unwrap();
+        assert_eq!(voting_machine.get_scoreboard().scores[&Candidate("Bob".to_string())].0, 2);
+    }
+
+    #[tokio::test]
+    async fn proxy_vote() {
+        let mut controller = setup_controller().await;
+
+        let proxy_form = ProxyForm {
+            principal: String::from("Claude"),
+            holder: String::from("Jane"),
+        };
+        let result = controller.grant_proxy(proxy_form).await.unwrap();
+        assert!(matches!(result, VoteOutcome::ProxyAccepted(_, _)));
+
+        let vote_form = VoteForm {
+            voter: String::from("Claude"),
+            candidate: String::from("Alice"),
+            code: String::new(),
+        };
+        let result = controller.vote_by_proxy("Jane", vote_form).await.unwrap();
+        assert!(matches!(result, VoteOutcome::AcceptedVote(_, _)));
+
+        let voting_machine = controller.get_voting_machine().await.unwrap();
+        assert_eq!(voting_machine.get_proxy_votes().get(&Voter("Claude".to_string())), Some(&Voter("Jane".to_string())));
+    }
+
+    #[tokio::test]
+    async fn concurrent_elections() {
+        let mut controller = setup_controller().await;
+        let referendum = VotingMachine::new(Scoreboard::new(vec![Candidate("Oui".to_string()), Candidate("Non".to_string())]));
+        controller.add_election("referendum", referendum.clone()).await.unwrap();
+        assert!(controller.add_election("referendum", referendum).await.is_err());
+
+        controller.vote(VoteForm { voter: String::from("Claude"), candidate: String::from("Alice"), code: String::new() }).await.unwrap();
+        controller.select_election("referendum").await.unwrap();
+        let result = controller.vote(VoteForm { voter: String::from("Claude"), candidate: String::from("Oui"), code: String::new() }).await.unwrap();
+        assert!(matches!(result, VoteOutcome::AcceptedVote(_, _)));
+        assert!(controller.select_election("inconnu").await.is_err());
+        assert_eq!(controller.current_election().0, "referendum");
+
+        let referendum = controller.get_voting_machine().await.unwrap();
+        assert_eq!(referendum.get_scoreboard().scores[&Candidate("Oui".to_string())].0, 1);
+        controller.select_election("principal").await.unwrap();
+        let principal = controller.get_voting_machine().await.unwrap();
+        assert_eq!(principal.get_scoreboard().scores[&Candidate("Alice".to_string())].0, 1);
+        assert_eq!(controller.election_ids().await.unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn lifecycle_and_hidden_results() {
+        let voting_machine = VotingMachine::new(Scoreboard::new(vec![Candidate("Alice".to_string()), Candidate("Bob".to_string())]))
+            .with_state(ElectionState::Draft)
+            .with_hidden_results(true);
+        let mut controller = VotingController::new(Memory::new(voting_machine).await.unwrap());
+
+        let result = controller.vote(VoteForm { voter: String::from("Claude"), candidate: String::from("Alice"), code: String::new() }).await.unwrap();
+        assert!(matches!(result, VoteOutcome::ElectionNotOpen(_, ElectionState::Draft)));
+
+        controller.transition(ElectionState::Open).await.unwrap();
+        let result = controller.vote(VoteForm { voter: String::from("Claude"), candidate: String::from("Alice"), code: String::new() }).await.unwrap();
+        assert!(matches!(result, VoteOutcome::AcceptedVote(_, _)));
+        assert!(controller.condorcet_winner().await.is_err());
+
+        controller.transition(ElectionState::Closed).await.unwrap();
+        assert_eq!(controller.condorcet_winner().await.unwrap(), Some(Candidate("Alice".to_string())));
+        let outcome = controller.transition(ElectionState::Certified).await.unwrap();
+        assert!(matches!(outcome, TransitionOutcome::Changed(ElectionState::Closed, ElectionState::Certified)));
+    }
+
+    #[tokio::test]
+    async fn scheduled_election() {
+        let voting_machine = VotingMachine::new(Scoreboard::new(vec![Candidate("Alice".to_string()), Candidate("Bob".to_string())]))
+            .with_state(ElectionState::Draft)
+            .with_schedule(Some(Timestamp(100)), Some(Timestamp(200)));
+        let storage = Memory::new(voting_machine).await.unwrap();
+        let mut controller = VotingController::new(storage).with_clock(Arc::new(FixedClock(Timestamp(150))));
+
+        assert_eq!(controller.get_voting_machine().await.unwrap().get_state(), ElectionState::Open);
+        let result = controller.vote(VoteForm { voter: String::from("Claude"), candidate: String::from("Alice"), code: String::new() }).await.unwrap();
+        assert!(matches!(result, VoteOutcome::AcceptedVote(_, _)));
+
+        let mut controller = controller.with_clock(Arc::new(FixedClock(Timestamp(250))));
+        let result = controller.vote(VoteForm { voter: String::from("Jane"), candidate: String::from("Alice"), code: String::new() }).await.unwrap();
+        assert!(matches!(result, VoteOutcome::AlreadyClosed(_, Timestamp(200))));
+        assert_eq!(controller.election_states().await.unwrap()[&ElectionId::default()], ElectionState::Closed);
+    }
+
+    #[tokio::test]
+    async fn turnout_against_the_roll() {
+        let roll = ElectoralRoll([Voter("Claude".to_string()), Voter("Jane".to_string())].into());
+        let voting_machine = VotingMachine::new(Scoreboard::new(vec![Candidate("Alice".to_string())])).with_roll(Some(roll));
+        let mut controller = VotingController::new(Memory::new(voting_machine).await.unwrap());
+
+        let result = controller.vote(VoteForm { voter: String::from("Mallory"), candidate: String::from("Alice"), code: String::new() }).await.unwrap();
+        assert!(matches!(result, VoteOutcome::NotEligible(_)));
+        controller.vote(VoteForm { voter: String::from("Claude"), candidate: String::from("Alice"), code: String::new() }).await.unwrap();
+
+        assert_eq!(controller.turnout().await.unwrap(), Some(Turnout { voted: 1, registered: 2 }));
+    }
+
+    #[tokio::test]
+    async fn voting_codes() {
+        let roll = ElectoralRoll([Voter("Claude".to_string())].into());
+        let voting_machine = VotingMachine::new(Scoreboard::new(vec![Candidate("Alice".to_string())]))
+            .with_state(ElectionState::Draft)
+            .with_roll(Some(roll));
+        let mut controller = VotingController::new(Memory::new(voting_machine).await.unwrap());
+        let CodesOutcome::Issued(codes) = controller.issue_codes().await.unwrap() else { panic!("codes should be issued") };
+        controller.transition(ElectionState::Open).await.unwrap();
+        let code = codes[&Voter("Claude".to_string())].0.clone();
+
+        let result = controller.vote(VoteForm { voter: String::from("Claude"), candidate: String::from("Alice"), code: String::from("WRONG") }).await.unwrap();
+        assert!(matches!(result, VoteOutcome::InvalidCode(_)));
+        let result = controller.vote(VoteForm { voter: String::from("Claude"), candidate: String::from("Alice"), code: code.clone() }).await.unwrap();
+        assert!(matches!(result, VoteOutcome::AcceptedVote(_, _)));
+        let result = controller.vote(VoteForm { voter: String::from("Claude"), candidate: String::from("Alice"), code }).await.unwrap();
+        assert!(matches!(result, VoteOutcome::CodeAlreadyUsed(_)));
+    }
+
+    #[tokio::test]
+    async fn revised_vote() {
+        let voting_machine = VotingMachine::new(Scoreboard::new(vec![Candidate("Alice".to_string()), Candidate("Bob".to_string())]))
+            .with_revision(true, Vec::new());
+        let mut controller = VotingController::new(Memory::new(voting_machine).await.unwrap());
+
+        controller.vote(VoteForm { voter: String::from("Claude"), candidate: String::from("Alice"), code: String::new() }).await.unwrap();
+        let result = controller.vote(VoteForm { voter: String::from("Claude"), candidate: String::from("Bob"), code: String::new() }).await.unwrap();
+        assert!(matches!(result, VoteOutcome::AcceptedVote(_, candidate) if candidate.0 == "Bob"));
+
+        let revisions = controller.revisions().await.unwrap();
+        assert_eq!(revisions.len(), 1);
+        assert_eq!(revisions[0].previous, Ballot::Single(Some(Candidate("Alice".to_string()))));
+    }
+
+    #[tokio::test]
+    async fn candidate_management() {
+        let voting_machine = VotingMachine::new(Scoreboard::new(vec![Candidate("Alice".to_string()), Candidate("Bob".to_string())]))
+            .with_state(ElectionState::Draft);
+        let mut controller = VotingController::new(Memory::new(voting_machine).await.unwrap());
+
+        assert!(matches!(controller.add_candidate("Charlie").await.unwrap(), CandidateOutcome::Added(_)));
+        assert!(matches!(controller.rename_candidate("Charlie", "Charles").await.unwrap(), CandidateOutcome::Renamed(_, _)));
+        controller.transition(ElectionState::Open).await.unwrap();
+        controller.vote(VoteForm { voter: String::from("Claude"), candidate: String::from("Bob"), code: String::new() }).await.unwrap();
+        let outcome = controller.withdraw_candidate("Bob", WithdrawalPolicy::Blank).await.unwrap();
+        assert!(matches!(outcome, CandidateOutcome::Withdrawn(_)));
+
+        let scoreboard = controller.get_voting_machine().await.unwrap().get_scoreboard().clone();
+        assert_eq!(scoreboard.scores.keys().cloned().collect::<Vec<_>>(), vec![Candidate("Alice".to_string()), Candidate("Charles".to_string())]);
+        assert_eq!(scoreboard.blank_score.0, 1);
+    }
+
+    #[tokio::test]
+    async fn write_in_management() {
+        let voting_machine = VotingMachine::new(Scoreboard::new(vec![Candidate("Alice".to_string()), Candidate("Bob".to_string())]))
+            .with_write_ins(true, Map::new());
+        let mut controller = VotingController::new(Memory::new(voting_machine).await.unwrap());
+
+        for (voter, candidate) in [("Claude", "charlie"), ("Jane", "Charly")] {
+            let outcome = controller.vote(VoteForm { voter: String::from(voter), candidate: String::from(candidate), code: String::new() }).await.unwrap();
+            assert!(matches!(outcome, VoteOutcome::WriteIn(_, _)));
+        }
+        assert!(matches!(controller.merge_write_ins("Charly", "Charlie").await.unwrap(), CandidateOutcome::Merged(_, _)));
+        assert!(matches!(controller.promote_write_in("charlie").await.unwrap(), CandidateOutcome::Promoted(_)));
+
+        let scoreboard = controller.get_voting_machine().await.unwrap().get_scoreboard().clone();
+        assert_eq!(scoreboard.scores[&Candidate("Charlie".to_string())].0, 2);
+        assert!(scoreboard.write_ins.is_empty());
+    }
 }
@@ -0,0 +1,44 @@
+use rand::Rng;
+use sha2::{Digest, Sha256};
+
+use crate::domain::{CodeHash, VotingCode};
+
+/// Characters codes are drawn from, without the ones easily mistaken for
+/// each other on paper (0/O, 1/I/L).
+const ALPHABET: &[u8] = b"23456789ABCDEFGHJKMNPQRSTUVWXYZ";
+const CODE_LENGTH: usize = 10;
+
+pub fn generate_code() -> VotingCode {
+    let mut rng = rand::thread_rng();
+    let code = (0..CODE_LENGTH)
+        .map(|_| char::from(ALPHABET[rng.gen_range(0..ALPHABET.len())]))
+        .collect();
+    VotingCode(code)
+}
+
+/// Hex-encoded SHA-256 digest of the code, ignoring surrounding spaces and
+/// letter case as typed by the voter.
+pub fn hash_code(code: &VotingCode) -> CodeHash {
+    let digest = Sha256::digest(code.0.trim().to_uppercase().as_bytes());
+    CodeHash(digest.iter().map(|byte| format!("{:02x}", byte)).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn codes_are_drawn_from_the_alphabet() {
+        let code = generate_code();
+        assert_eq!(code.0.len(), CODE_LENGTH);
+        assert!(code.0.bytes().all(|byte| ALPHABET.contains(&byte)));
+    }
+
+    #[test]
+    fn hashes_ignore_case_and_spaces() {
+        let hash = hash_code(&VotingCode(String::from("ABC234")));
+        assert_eq!(hash, hash_code(&VotingCode(String::from(" abc234 "))));
+        assert_ne!(hash, hash_code(&VotingCode(String::from("ABC235"))));
+        assert_eq!(hash_code(&VotingCode(String::new())).0, "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855");
+    }
+}